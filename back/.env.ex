@@ -5,5 +5,24 @@ WEB_API_KEY="42"
 NETWORKS="ethereum,unichain"
 HEARTBEATS=""
 API_PORT=42042
+# Storage backend: "redis" (default, REDIS_HOST) or "memory" (single process, no Redis needed)
+STORE="redis"
+# Prefix of every store key, to share a Redis instance between deployments (e.g. staging and prod)
+KEY_PREFIX="tycho-orderbook"
+# Delete the unprefixed keys of schema v1 ('stream:*') when migrating. Only if no other deployment on this Redis instance still runs v1
+DROP_LEGACY_KEYS=false
+# Store codec: "json" (default) or "msgpack", optionally zstd-compressed. Values written with any codec stay readable
+CODEC="json"
+CODEC_ZSTD=false
+# TTL (seconds) of cached orderbooks
+CACHE_OB_DURATION=300
+# Delay (seconds) between two checkpoints of the streamed tokens and components, restored and served (stale) after a restart. 0 to disable
+CHECKPOINT_INTERVAL=300
+# Delay (seconds) between two refreshes of the Tycho tokens, new tokens restarting the stream. 0 to disable
+TOKENS_REFRESH_INTERVAL=3600
+# Restart policy of the stream tasks (seconds), exponential backoff with jitter. Per network with RESTART_POLICY_<NETWORK> (e.g. RESTART_POLICY_ETHEREUM)
+RESTART_POLICY="min=5,max=150,factor=2,jitter=0.2"
+# Protocol, TVL (native token) and token quality filters of the streams, unset = no filtering. Per network with STREAM_FILTER_<NETWORK> (e.g. STREAM_FILTER_BASE)
+# STREAM_FILTER="allow=uniswap_v2|uniswap_v3,deny=vm:curve,min_tvl=10,min_token_quality=100,max_days_since_last_trade=30"
 
 # Copy-paste this in a .env file to launch the API.
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! Round-trip latency of each store codec on the components of a network
+//! Reads the components hash written by a running stream (same STORE / REDIS_HOST env as the API), then for each codec:
+//! encode, write to a temporary key, read it back and decode, averaged over BENCH_ITERATIONS runs
+//! Bincode is measured for comparison only, it can't be selected with CODEC (see Format)
+//! Usage: NETWORK=ethereum BENCH_ITERATIONS=20 cargo bench --bench codec
+
+use std::time::Instant;
+
+use shared::{
+    codec::{Codec, Format},
+    data::keys,
+    types::ComponentEntry,
+};
+use tycho_orderbook::data::fmt::SrzProtocolComponent;
+
+#[tokio::main]
+async fn main() {
+    let network = std::env::var("NETWORK").unwrap_or("ethereum".to_string());
+    let iterations = std::env::var("BENCH_ITERATIONS").ok().and_then(|x| x.parse::<u32>().ok()).unwrap_or(20).max(1);
+    let store = shared::store::from_env();
+    let key = keys::stream::components(network.clone());
+    let entries = match shared::data::hvals::<ComponentEntry>(&store, key.as_str()).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("No components read for '{}' ({}). Launch the stream first.", network, e);
+            return;
+        }
+    };
+    let components = entries.into_iter().map(|x| x.component).collect::<Vec<SrzProtocolComponent>>();
+    println!("{} components on {}, {} iterations per codec", components.len(), network, iterations);
+    println!(
+        "{:<16} {:>12} {:>12} {:>12} {:>12} {:>12} {:>14}",
+        "codec", "size (KB)", "encode (ms)", "set (ms)", "get (ms)", "decode (ms)", "round-trip (ms)"
+    );
+    let bench = format!("bench:codec:{}", network.to_lowercase());
+    let codecs = [Format::Json, Format::MsgPack, Format::Bincode]
+        .iter()
+        .flat_map(|format| [false, true].map(|zstd| Codec { format: *format, zstd }))
+        .collect::<Vec<Codec>>();
+    for codec in codecs {
+        let (mut encode, mut set, mut get, mut decode) = (0u128, 0u128, 0u128, 0u128);
+        let mut size = 0;
+        let mut failed = None;
+        for _ in 0..iterations {
+            let time = Instant::now();
+            let raw = match codec.encode(&components) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    failed = Some(e);
+                    break;
+                }
+            };
+            encode += time.elapsed().as_micros();
+            size = raw.len();
+            let time = Instant::now();
+            if let Err(e) = store.set(bench.as_str(), raw).await {
+                failed = Some(e.to_string());
+                break;
+            }
+            set += time.elapsed().as_micros();
+            let time = Instant::now();
+            let raw = store.get(bench.as_str()).await.ok().flatten().unwrap_or_default();
+            get += time.elapsed().as_micros();
+            let time = Instant::now();
+            if let Err(e) = Codec::decode::<Vec<SrzProtocolComponent>>(raw.as_slice()) {
+                failed = Some(e);
+                break;
+            }
+            decode += time.elapsed().as_micros();
+        }
+        let name = format!("{:?}{}", codec.format, if codec.zstd { "+zstd" } else { "" }).to_lowercase();
+        match failed {
+            Some(e) => println!("{:<16} failed: {}", name, e),
+            None => {
+                let ms = |x: u128| x as f64 / iterations as f64 / 1000.;
+                println!(
+                    "{:<16} {:>12.1} {:>12.3} {:>12.3} {:>12.3} {:>12.3} {:>14.3}",
+                    name,
+                    size as f64 / 1024.,
+                    ms(encode),
+                    ms(set),
+                    ms(get),
+                    ms(decode),
+                    ms(encode + set + get + decode)
+                );
+            }
+        }
+    }
+    let _ = store.delete(bench.as_str()).await;
+}
@@ -92,9 +92,15 @@ try "GET /networks" "$API_URL/networks"
 
 # Test endpoints that require a network
 try "GET /$network/status" "$API_URL/$network/status"
+try "GET /$network/stream" "$API_URL/$network/stream"
 try "GET /$network/tokens" "$API_URL/$network/tokens"
+try "GET /$network/feed/tokens" "$API_URL/$network/feed/tokens"
 try "GET /$network/components" "$API_URL/$network/components"
 try "GET /$network/pairs" "$API_URL/$network/pairs"
+try "GET /$network/components (filtered)" "$API_URL/$network/components?protocol_system=uniswap_v3&token=$usdc&sort=-updated&limit=10"
+try "GET /$network/tokens/USDC" "$API_URL/$network/tokens/USDC"
+try "GET /$network/tokens (filtered)" "$API_URL/$network/tokens?symbol=USDC"
+try "GET /$network/pairs (paginated)" "$API_URL/$network/pairs?token=$eth&limit=20&cursor=20"
 
 # Test simulations
 try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$eth-$usdc"'"}'
@@ -103,6 +109,8 @@ try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "
 # try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$eth-$usdt"'"}'
 # try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$usdc-$wbtc"'"}'
 try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$usdc-$dai"'"}'
+try "POST /$network/orderbook (symbols)" "$API_URL/$network/orderbook" '{"tag": "WETH-USDC"}'
+try "POST /$network/orderbook (max age)" "$API_URL/$network/orderbook" '{"tag": "'"$eth-$usdc"'", "max_age": 0}'
 try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$usdc-$usdt"'"}'
 # try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$wbtc-$dai"'"}'
 # try "POST /$network/orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$wbtc-$usdt"'"}'
@@ -118,6 +126,8 @@ try "POST /$network/orderbook (with point)" "$API_URL/$network/orderbook" '{"tag
 # try "POST /$network/orderbook (with point)" "$API_URL/$network/orderbook" '{"tag": "'"$wbtc-$dai"'", "point": {"input": "'"$wbtc"'", "amount": 1}}'
 # try "POST /$network/orderbook (with point)" "$API_URL/$network/orderbook" '{"tag": "'"$wbtc-$usdt"'", "point": {"input": "'"$wbtc"'", "amount": 1}}'
 
+try "GET /$network/quote" "$API_URL/$network/quote?sell=$eth&buy=$usdc&amount=3"
+
 # usdp="0x8e870d67f660d95d5be530380d0ec0bd388289e1" # Trying when no orderbook available
 # try "POST /orderbook (simple)" "$API_URL/$network/orderbook" '{"tag": "'"$eth-$usdp"'"}'
 
@@ -1,20 +1,39 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
 use axum::{
-    extract::Json as AxumExJson,
-    http::{self, HeaderMap},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Json as AxumExJson, Path, Query,
+    },
+    http::{self, HeaderMap, StatusCode},
     routing::{get, post},
     Extension, Json as AxumJson, Router,
 };
 
-use axum::response::IntoResponse;
+use axum::response::{
+    sse::{Event, KeepAlive, Sse},
+    IntoResponse, Response,
+};
 
 use http::HeaderValue;
 use serde_json::json;
 use shared::{
     data::keys,
     getters,
-    helpers::{prevalidation, validate_headers},
-    types::{APIResponse, EnvAPIConfig, PairTag, Status, Version},
+    helpers::{filter_components, filter_pairs, filter_tokens, paginate, prevalidation, resolve, validate_headers},
+    invalidation::Index,
+    misc::r#static::{MAX_BATCH_ORDERBOOKS, MAX_PAGE_LIMIT},
+    store::{Store, StoreError},
+    supervisor::{Supervisor, Supervisors},
+    types::{
+        APIResponse, ApiError, BatchOrderbooks, BlockEvent, CachedOrderbook, ComponentDetail, EnvAPIConfig, FeedParams, HopLevel, ListParams, MultiHopOrderbook, NetworkConfig, OrderbookQuery,
+        OrderbookResponse, OrderbookStreamParams, Page, PairTag, Quote, QuoteParams, QuoteSplit, RouteHop, SpotPrice, Status, StreamFilters, StreamMetrics, StreamState, TokenDiff, Version,
+    },
 };
+use tokio::sync::broadcast::{self, error::RecvError};
 use tower_http::cors::{Any, CorsLayer};
 use tycho_orderbook::{
     core::{book, exec, helper::get_original_components, solver::DefaultOrderbookSolver},
@@ -23,6 +42,8 @@ use tycho_orderbook::{
     types::{ExecutionRequest, Network, Orderbook, OrderbookRequestParams, ProtoSimComp, SharedTychoStreamState, SrzExecutionPayload, SrzTransactionRequest},
     utils::misc::current_timestamp,
 };
+use tycho_simulation::models::Token;
+use tycho_simulation::protocol::models::ProtocolComponent;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -38,14 +59,22 @@ use utoipa_swagger_ui::SwaggerUi;
         version,
         networks,
         status,
+        stream,
         tokens,
+        token,
         components,
+        component,
         pairs,
         orderbook,
-        execute
+        orderbooks,
+        quote,
+        execute,
+        events,
+        ws_orderbook,
+        token_feed
     ),
     components(
-        schemas(Version, Network, Status, SrzToken, SrzProtocolComponent, Orderbook, ExecutionRequest, PairTag)
+        schemas(Version, Network, NetworkConfig, StreamFilters, Status, StreamMetrics, SrzToken, SrzProtocolComponent, Orderbook, OrderbookQuery, OrderbookResponse, CachedOrderbook, ExecutionRequest, PairTag, BlockEvent, StreamState, ApiError, MultiHopOrderbook, HopLevel, RouteHop, BatchOrderbooks, Quote, QuoteSplit, ComponentDetail, SpotPrice, TokenDiff)
     ),
     servers(
         (url = "/api", description = "Root API"),
@@ -58,31 +87,34 @@ use utoipa_swagger_ui::SwaggerUi;
 )]
 struct APIDoc;
 
-pub fn wrap<T: serde::Serialize>(data: Option<T>, error: Option<String>) -> impl IntoResponse {
+/// Wrap the data or the error into the APIResponse, with the HTTP status code matching the error
+pub fn wrap<T: serde::Serialize>(data: Option<T>, error: Option<ApiError>) -> Response {
     match error {
         Some(err) => {
             let response = APIResponse::<String> {
                 success: false,
-                error: err.clone(),
+                error: err.message(),
+                code: Some(err.code().to_string()),
                 data: None,
                 ts: current_timestamp(),
             };
-            AxumJson(json!(response))
+            (err.status(), AxumJson(json!(response))).into_response()
         }
         None => {
             let response = APIResponse {
                 success: true,
                 error: String::default(),
+                code: None,
                 data,
                 ts: current_timestamp(),
             };
-            AxumJson(json!(response))
+            (StatusCode::OK, AxumJson(json!(response))).into_response()
         }
     }
 }
 
 // GET / => "Hello, Tycho!"
-async fn root() -> impl IntoResponse {
+async fn root() -> Response {
     wrap(Some("Gm!"), None)
 }
 
@@ -92,17 +124,18 @@ async fn root() -> impl IntoResponse {
     path = "/version",
     summary = "API version",
     responses(
-        (status = 200, description = "API Version", body = Version)
+        (status = 200, description = "API Version", body = Version),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>)
     ),
     tag = (
         "API"
     )
 )]
-async fn version(headers: HeaderMap, Extension(config): Extension<EnvAPIConfig>) -> impl IntoResponse {
+async fn version(headers: HeaderMap, Extension(config): Extension<EnvAPIConfig>) -> Response {
     tracing::info!("👾 API: GET /version");
     let (allowed, msg) = validate_headers(&headers, config.web_api_key);
     if !allowed {
-        return wrap(None, Some(msg));
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
     }
     wrap(Some(Version { version: "0.1.0".into() }), None)
 }
@@ -113,17 +146,18 @@ async fn version(headers: HeaderMap, Extension(config): Extension<EnvAPIConfig>)
     path = "/network",
     summary = "Network configuration",
     responses(
-        (status = 200, description = "Network configuration", body = Vec<Network>)
+        (status = 200, description = "Network configuration, with the protocol, TVL and token filters of its stream", body = Vec<NetworkConfig>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>)
     ),
     tag = (
         "API"
     )
 )]
-async fn networks(headers: HeaderMap, Extension(network): Extension<Vec<Network>>, Extension(config): Extension<EnvAPIConfig>) -> impl IntoResponse {
+async fn networks(headers: HeaderMap, Extension(network): Extension<Vec<NetworkConfig>>, Extension(config): Extension<EnvAPIConfig>) -> Response {
     tracing::info!("👾 API: GET /networks");
     let (allowed, msg) = validate_headers(&headers, config.web_api_key);
     if !allowed {
-        return wrap(None, Some(msg));
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
     }
     wrap(Some(network.clone()), None)
 }
@@ -135,49 +169,126 @@ async fn networks(headers: HeaderMap, Extension(network): Extension<Vec<Network>
     summary = "API status and latest block synchronized",
     description = "API is 'running' when Redis and Stream are ready. Block updated at each new header after processing state updates",
     responses(
-        (status = 200, description = "Current API status and latest block synchronized, along with last block updated components", body = Status)
+        (status = 200, description = "Current API status and latest block synchronized, along with last block updated components. While stale, lists are served from the checkpoint but simulations answer not_initialised, protosims aren't checkpointed", body = Status),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 500, description = "Stored data unreadable (code: corrupted_data)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised) or storage unavailable (code: storage)", body = APIResponse<String>)
     ),
     tag = (
         "API"
     )
 )]
-async fn status(headers: HeaderMap, Extension(network): Extension<Network>, Extension(config): Extension<EnvAPIConfig>) -> impl IntoResponse {
+async fn status(headers: HeaderMap, Extension(network): Extension<Network>, Extension(store): Extension<Store>, Extension(config): Extension<EnvAPIConfig>) -> Response {
     tracing::info!("👾 API: GET /status on {} network", network.name);
     let (allowed, msg) = validate_headers(&headers, config.web_api_key);
     if !allowed {
-        return wrap(None, Some(msg));
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
     }
-    match getters::status(network.clone()).await {
-        Some(data) => wrap(Some(data), None),
-        _ => wrap(None, Some("Failed to get status".to_string())),
+    match getters::status(&store, network.clone()).await {
+        Ok(data) => wrap(Some(data), None),
+        Err(e) => wrap(None, Some(e.into())),
     }
 }
 
+// GET /stream => Lifecycle metrics of the stream task
+#[utoipa::path(
+    get,
+    path = "/stream",
+    summary = "Lifecycle metrics of the network stream",
+    description = "Current state and time spent in each state, blocks processed, messages per second and time since the last message, restart count and last error of the stream task, since launch. Reported by the process running the streams",
+    responses(
+        (status = 200, description = "Stream task metrics", body = StreamMetrics),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn stream(headers: HeaderMap, Extension(network): Extension<Network>, Extension(supervisor): Extension<Arc<Supervisor>>, Extension(config): Extension<EnvAPIConfig>) -> Response {
+    tracing::info!("👾 API: GET /stream on {} network", network.name);
+    let (allowed, msg) = validate_headers(&headers, config.web_api_key);
+    if !allowed {
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
+    }
+    wrap(Some(supervisor.report()), None)
+}
+
 // GET /tokens => Get tokens object from Tycho
 #[utoipa::path(
     get,
     path = "/tokens",
     summary = "All Tycho tokens on the network",
-    description = "Only quality tokens are listed here (evaluated at 100 by Tycho = no rebasing, etc)",
+    description = "Only quality tokens are listed here (evaluated at 100 by Tycho = no rebasing, etc). Filterable by token address and symbol, sortable by address or symbol, paginated with limit and cursor",
+    params(ListParams),
     responses(
-        (status = 200, description = "Tycho Tokens on the network", body = Vec<SrzToken>)
+        (status = 200, description = "Tycho Tokens on the network", body = Page<SrzToken>),
+        (status = 400, description = "Invalid sort, limit or cursor (code: bad_request)", body = APIResponse<String>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 500, description = "Stored data unreadable (code: corrupted_data)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised) or storage unavailable (code: storage)", body = APIResponse<String>)
     ),
     tag = (
         "API"
     )
 )]
-async fn tokens(headers: HeaderMap, Extension(network): Extension<Network>, Extension(config): Extension<EnvAPIConfig>) -> impl IntoResponse {
-    tracing::info!("👾 API: GET /tokens on {} network", network.name);
+async fn tokens(
+    headers: HeaderMap,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(config): Extension<EnvAPIConfig>,
+    Query(params): Query<ListParams>,
+) -> Response {
+    tracing::info!("👾 API: GET /tokens on {} network | {:?}", network.name, params);
     let (allowed, msg) = validate_headers(&headers, config.web_api_key);
     if !allowed {
-        return wrap(None, Some(msg));
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
     }
-    match getters::tokens(network.clone()).await {
-        Some(tokens) => {
-            tracing::debug!("Returning {} tokens", tokens.len());
-            wrap(Some(tokens), None)
-        }
-        _ => wrap(None, Some("Failed to get tokens".to_string())),
+    match getters::tokens(&store, network.clone()).await {
+        Ok(tokens) => match filter_tokens(tokens, &params).and_then(|tokens| paginate(tokens, &params)) {
+            Ok(mut page) => {
+                page.stale = getters::stale(&store, network).await;
+                tracing::debug!("Returning {} tokens out of {}", page.items.len(), page.total);
+                wrap(Some(page), None)
+            }
+            Err(e) => wrap(None, Some(e)),
+        },
+        Err(e) => wrap(None, Some(e.into())),
+    }
+}
+
+// GET /tokens/{address_or_symbol} => Get one token
+#[utoipa::path(
+    get,
+    path = "/tokens/{address_or_symbol}",
+    summary = "One Tycho token, by address or symbol",
+    description = "Symbol lookup is case insensitive. When several tokens share the symbol, the error lists the candidate addresses",
+    params(
+        ("address_or_symbol" = String, Path, description = "Token address (0x..) or symbol (e.g. WETH)")
+    ),
+    responses(
+        (status = 200, description = "Tycho Token", body = SrzToken),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 404, description = "Unknown token (code: unknown_token)", body = APIResponse<String>),
+        (status = 409, description = "Symbol shared by several tokens, candidates listed in the error (code: ambiguous_symbol)", body = APIResponse<String>),
+        (status = 500, description = "Stored data unreadable (code: corrupted_data)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised) or storage unavailable (code: storage)", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn token(headers: HeaderMap, Extension(network): Extension<Network>, Extension(store): Extension<Store>, Extension(config): Extension<EnvAPIConfig>, Path(query): Path<String>) -> Response {
+    tracing::info!("👾 API: GET /tokens/{} on {} network", query, network.name);
+    let (allowed, msg) = validate_headers(&headers, config.web_api_key);
+    if !allowed {
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
+    }
+    match getters::tokens(&store, network.clone()).await {
+        Ok(tokens) => match resolve(&tokens, query.as_str()) {
+            Ok(token) => wrap(Some(token), None),
+            Err(e) => wrap(None, Some(e)),
+        },
+        Err(e) => wrap(None, Some(e.into())),
     }
 }
 
@@ -186,28 +297,55 @@ async fn tokens(headers: HeaderMap, Extension(network): Extension<Network>, Exte
     get,
     path = "/pairs",
     summary = "Tycho pairs (0xETH-0xUSDC, with addresses), etc.",
-    description = "Returns all pairs available on the network, based on the components (filtered)",
+    description = "Returns all pairs available on the network, based on the components (filtered). Components can be filtered by protocol_system and updated_since before generating the pairs, pairs by token address and symbol. Sortable by base or quote, paginated with limit and cursor",
+    params(ListParams),
     responses(
-        (status = 200, description = "Tycho Pairs", body = Vec<PairTag>)
+        (status = 200, description = "Tycho Pairs", body = Page<PairTag>),
+        (status = 400, description = "Invalid sort, limit or cursor (code: bad_request)", body = APIResponse<String>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 500, description = "Stored data unreadable (code: corrupted_data)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised) or storage unavailable (code: storage)", body = APIResponse<String>)
     ),
     tag = (
         "API"
     )
 )]
-async fn pairs(headers: HeaderMap, Extension(network): Extension<Network>, Extension(config): Extension<EnvAPIConfig>) -> impl IntoResponse {
-    tracing::info!("👾 API: GET /pairs on {} network", network.name);
+async fn pairs(
+    headers: HeaderMap,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(config): Extension<EnvAPIConfig>,
+    Query(params): Query<ListParams>,
+) -> Response {
+    tracing::info!("👾 API: GET /pairs on {} network | {:?}", network.name, params);
     let (allowed, msg) = validate_headers(&headers, config.web_api_key);
     if !allowed {
-        return wrap(None, Some(msg));
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
     }
-    match getters::pairs(network).await {
-        Some(pairs) => {
-            tracing::debug!("Returning {} pairs", pairs.len());
-            wrap(Some(pairs), None)
+    match getters::components(&store, network.clone()).await {
+        Ok(cps) => {
+            // Only component level filters apply before generating the pairs
+            let scope = ListParams {
+                protocol_system: params.protocol_system.clone(),
+                updated_since: params.updated_since,
+                ..Default::default()
+            };
+            let result = filter_components(cps, &scope)
+                .map(|cps| shared::helpers::generate_pair_tags(&cps))
+                .and_then(|pairs| filter_pairs(pairs, &params))
+                .and_then(|pairs| paginate(pairs, &params));
+            match result {
+                Ok(mut page) => {
+                    page.stale = getters::stale(&store, network).await;
+                    tracing::debug!("Returning {} pairs out of {}", page.items.len(), page.total);
+                    wrap(Some(page), None)
+                }
+                Err(e) => wrap(None, Some(e)),
+            }
         }
-        _ => {
-            let msg = "Failed to generate pair tags";
-            wrap(None, Some(msg.to_string()))
+        Err(e) => {
+            tracing::error!("Failed to generate pair tags: {}", e);
+            wrap(None, Some(e.into()))
         }
     }
 }
@@ -217,30 +355,117 @@ async fn pairs(headers: HeaderMap, Extension(network): Extension<Network>, Exten
     get,
     path = "/components",
     summary = "Tycho components (= liquidity pools)",
-    description = "Returns all components available on the network",
+    description = "Returns all components available on the network. Filterable by protocol_system, token address, token symbol and updated_since, sortable by id, protocol or updated, paginated with limit and cursor",
+    params(ListParams),
     responses(
-        (status = 200, description = "Tycho Components (= liquidity pools)", body = SrzProtocolComponent)
+        (status = 200, description = "Tycho Components (= liquidity pools)", body = Page<SrzProtocolComponent>),
+        (status = 400, description = "Invalid sort, limit or cursor (code: bad_request)", body = APIResponse<String>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 500, description = "Stored data unreadable (code: corrupted_data)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised) or storage unavailable (code: storage)", body = APIResponse<String>)
     ),
     tag = (
         "API"
     )
 )]
-async fn components(headers: HeaderMap, Extension(network): Extension<Network>, Extension(config): Extension<EnvAPIConfig>) -> impl IntoResponse {
-    tracing::info!("👾 API: GET /components on {} network", network.name);
+async fn components(
+    headers: HeaderMap,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(config): Extension<EnvAPIConfig>,
+    Query(params): Query<ListParams>,
+) -> Response {
+    tracing::info!("👾 API: GET /components on {} network | {:?}", network.name, params);
     let (allowed, msg) = validate_headers(&headers, config.web_api_key);
     if !allowed {
-        return wrap(None, Some(msg));
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
     }
-    match getters::components(network).await {
-        Some(cps) => {
-            tracing::debug!("Returning {} components", cps.len());
-            wrap(Some(cps), None)
+    match getters::components(&store, network.clone()).await {
+        Ok(cps) => match filter_components(cps, &params).and_then(|cps| paginate(cps, &params)) {
+            Ok(mut page) => {
+                page.stale = getters::stale(&store, network).await;
+                tracing::debug!("Returning {} components out of {}", page.items.len(), page.total);
+                wrap(Some(page), None)
+            }
+            Err(e) => wrap(None, Some(e)),
+        },
+        Err(e) => {
+            tracing::error!("Failed to get components: {}", e);
+            wrap(None, Some(e.into()))
         }
-        _ => {
-            tracing::error!("Failed to get components");
-            wrap(None, Some("Failed to get components".to_string()))
+    }
+}
+
+// GET /components/{id} => Get one component and its live state
+#[utoipa::path(
+    get,
+    path = "/components/{id}",
+    summary = "One Tycho component, with its live state",
+    description = "Returns the component along with spot prices in both directions, fee, and the block and timestamp of its last update",
+    params(
+        ("id" = String, Path, description = "Component id (pool address or pool id)")
+    ),
+    responses(
+        (status = 200, description = "Tycho Component and its live state", body = ComponentDetail),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 404, description = "Unknown component (code: unknown_component)", body = APIResponse<String>),
+        (status = 500, description = "Stored data unreadable (code: corrupted_data)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised) or storage unavailable (code: storage)", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn component(
+    headers: HeaderMap,
+    Extension(shtss): Extension<SharedTychoStreamState>,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(config): Extension<EnvAPIConfig>,
+    Path(id): Path<String>,
+) -> Response {
+    tracing::info!("👾 API: GET /components/{} on {} network", id, network.name);
+    let mtx = shtss.read().await;
+    let initialised = mtx.initialised;
+    drop(mtx);
+    if let Some(e) = prevalidation(&store, network.clone(), headers.clone(), initialised, config.web_api_key.clone()).await {
+        return wrap(None, Some(e));
+    }
+    let entry = match getters::component(&store, network.clone(), id.clone()).await {
+        Ok(entry) => entry,
+        Err(StoreError::NotFound(_)) => return wrap(None, Some(ApiError::UnknownComponent(format!("Component {} not found", id)))),
+        Err(e) => return wrap(None, Some(e.into())),
+    };
+    let mtx = shtss.read().await;
+    let protosim = mtx.protosims.get(&id.to_lowercase()).cloned();
+    drop(mtx);
+    let Some(protosim) = protosim else {
+        return wrap(None, Some(ApiError::UnknownComponent(format!("No state found for component {}", id))));
+    };
+    let mut prices = vec![];
+    for base in entry.component.tokens.iter() {
+        for quote in entry.component.tokens.iter() {
+            if base.address.to_lowercase() == quote.address.to_lowercase() {
+                continue;
+            }
+            match protosim.spot_price(&Token::from(base.clone()), &Token::from(quote.clone())) {
+                Ok(price) => prices.push(SpotPrice {
+                    base: base.address.to_lowercase(),
+                    quote: quote.address.to_lowercase(),
+                    price,
+                }),
+                Err(e) => tracing::debug!("Spot price {} => {} failed on {}: {:?}", base.symbol, quote.symbol, id, e),
+            }
         }
     }
+    let detail = ComponentDetail {
+        block: entry.block,
+        timestamp: entry.component.last_updated_at,
+        fee: protosim.fee(),
+        prices,
+        component: entry.component,
+    };
+    wrap(Some(detail), None)
 }
 
 // POST /execute => Execute a trade
@@ -251,24 +476,31 @@ async fn components(headers: HeaderMap, Extension(network): Extension<Network>,
     request_body = ExecutionRequest,
     description = "Using Tycho execution engine, build a transaction according to a given orderbook point/distribution",
     responses(
-        (status = 200, description = "The trade result", body = SrzExecutionPayload)
+        (status = 200, description = "The trade result", body = SrzExecutionPayload),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 500, description = "Transaction building failed (code: execution_failed)", body = APIResponse<String>)
     ),
     tag = ("API")
 )]
 async fn execute(
     headers: HeaderMap,
     Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
     Extension(state): Extension<SharedTychoStreamState>,
     Extension(config): Extension<EnvAPIConfig>,
     AxumExJson(execution): AxumExJson<ExecutionRequest>,
-) -> impl IntoResponse {
+) -> Response {
     tracing::info!("👾 API: {} : Querying execute endpoint: {:?}", network.name, execution);
-    if let Some(e) = prevalidation(network.clone(), headers.clone(), true, config.web_api_key).await {
+    if let Some(e) = prevalidation(&store, network.clone(), headers.clone(), true, config.web_api_key).await {
         return wrap(None, Some(e));
     }
-    // Get the original components from the state
+    // Get the original components from the state, keyed by the requested ids (the shared state is keyed by lowercased id)
     let mtx = state.read().await;
-    let originals = mtx.components.clone();
+    let originals = execution
+        .components
+        .iter()
+        .filter_map(|x| shared::sync::component(&mtx, x.id.as_str()).map(|cp| (x.id.clone(), cp.clone())))
+        .collect::<HashMap<String, ProtocolComponent>>();
     drop(mtx);
     let originals = get_original_components(originals, execution.components.clone());
     match exec::create(network.clone(), execution.clone(), originals, None).await {
@@ -281,7 +513,7 @@ async fn execute(
         }
         Err(e) => {
             let error = e.to_string();
-            wrap(None, Some(error))
+            wrap(None, Some(ApiError::ExecutionFailed(error)))
         }
     }
 }
@@ -291,10 +523,17 @@ async fn execute(
     post,
     path = "/orderbook",
     summary = "Orderbook for a given pair of tokens",
-    description = "Aggregate liquidity across AMMs, simulates an orderbook (bids/asks). Depending on the number of components (pool having t0 AND t1) and simulation input config, the orderbook can be more or less accurate, and the simulation can take up to severals minutes",
-    request_body = OrderbookRequestParams,
+    description = "Aggregate liquidity across AMMs, simulates an orderbook (bids/asks). Tag is either addresses (0xt0-0xt1) or symbols (WETH-USDC). Depending on the number of components (pool having t0 AND t1) and simulation input config, the orderbook can be more or less accurate, and the simulation can take up to severals minutes. When the pair has no direct pool, a synthetic multi-hop orderbook is simulated through intermediate tokens (full orderbooks only). The 'kind' field tells them apart: 'direct' or 'multihop'",
+    request_body = OrderbookQuery,
     responses(
-        (status = 200, description = "Contains trade simulations, results and components", body = Orderbook)
+        (status = 200, description = "Direct orderbook (kind: direct) with trade simulations, results and components, and whether it was served from the cache (stale if computed before a restart, while the stream syncs). Multi-hop orderbook (kind: multihop) for a pair without direct pool", body = OrderbookResponse),
+        (status = 400, description = "Malformed tag (code: bad_request)", body = APIResponse<String>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 404, description = "Unknown token (code: unknown_token) or, for a single point simulation, pair without pools (code: no_pools)", body = APIResponse<String>),
+        (status = 409, description = "Symbol shared by several tokens, candidates listed in the error (code: ambiguous_symbol)", body = APIResponse<String>),
+        (status = 422, description = "No path found between tokens, direct or multi-hop (code: routing_failed)", body = APIResponse<String>),
+        (status = 500, description = "Orderbook simulation failed (code: simulation_failed) or stored data unreadable (code: corrupted_data)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised, or no protosim yet for the pools of the pair (code: not_initialised), or storage unavailable (code: storage). Protosims aren't checkpointed: after a restart, only an orderbook cached before it can be served (stale) until the first block", body = APIResponse<String>)
     ),
     tag = (
         "API"
@@ -304,160 +543,764 @@ async fn orderbook(
     headers: HeaderMap,
     Extension(shtss): Extension<SharedTychoStreamState>,
     Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(index): Extension<Index>,
     Extension(config): Extension<EnvAPIConfig>,
-    AxumExJson(params): AxumExJson<OrderbookRequestParams>,
-) -> impl IntoResponse {
+    AxumExJson(query): AxumExJson<OrderbookQuery>,
+) -> Response {
+    let params = query.params.clone();
     let single = params.point.is_some();
-    tracing::info!("👾 API: {} : OrderbookRequestParams: {:?} | Single: {}", network.name, params, single);
+    tracing::info!("👾 API: {} : OrderbookRequestParams: {:?} | Single: {} | Max age: {:?}", network.name, params, single, query.max_age);
     let mtx = shtss.read().await;
     let initialised = mtx.initialised;
     drop(mtx);
-    if let Some(e) = prevalidation(network.clone(), headers.clone(), initialised, config.web_api_key).await {
-        return wrap(None, Some(e));
+    match prevalidation(&store, network.clone(), headers.clone(), initialised, config.web_api_key.clone()).await {
+        // Stream syncing after a restart: serve the orderbook cached before it, if any
+        Some(ApiError::NotInitialised(msg)) if !single && validate_headers(&headers, config.web_api_key.clone()).0 => {
+            return match stale(&store, network.clone(), params.tag.as_str()).await {
+                Ok(result) => wrap(Some(OrderbookResponse::Direct(result)), None),
+                Err(e) => {
+                    tracing::debug!("No stale orderbook to serve: {}", e);
+                    wrap(None, Some(ApiError::NotInitialised(msg)))
+                }
+            };
+        }
+        Some(e) => return wrap(None, Some(e)),
+        None => {}
     }
+    match compute(&store, &index, network.clone(), shtss.clone(), config, params.clone(), query.max_age).await {
+        Ok(result) => wrap(Some(result), None),
+        Err(e) => wrap(None, Some(e)),
+    }
+}
 
-    match (getters::tokens(network.clone()).await, getters::components(network.clone()).await) {
-        (Some(atks), Some(acps)) => {
-            let target = params.tag.clone();
-            let targets = target.split("-").map(|x| x.to_string().to_lowercase()).collect::<Vec<String>>();
-            let srzt0 = atks.iter().find(|x| x.address.to_lowercase() == targets[0].clone().to_lowercase());
-            let srzt1 = atks.iter().find(|x| x.address.to_lowercase() == targets[1].clone().to_lowercase());
-            if srzt0.is_none() {
-                let msg = "Couldn't find tokens[0]".to_string();
-                tracing::error!("{}", msg.clone());
-                return wrap(None, Some(msg.to_string()));
-            } else if srzt1.is_none() {
-                let msg = "Couldn't find tokens[1]".to_string();
-                tracing::error!("{}", msg.clone());
-                return wrap(None, Some(msg.to_string()));
+/// Orderbook cached before a restart, while the stream syncs and no protosim is available to simulate a fresh one
+/// Cached orderbooks keep their TTL across restarts, so nothing older than CACHE_OB_DURATION is served
+async fn stale(store: &Store, network: Network, tag: &str) -> Result<CachedOrderbook, ApiError> {
+    let atks = getters::tokens(store, network.clone()).await?;
+    let (base, quote) = shared::helpers::pair(&atks, tag)?;
+    let tag = format!("{}-{}", base.address.to_lowercase(), quote.address.to_lowercase());
+    let key = keys::stream::orderbook(network.name.clone(), tag);
+    let orderbook = shared::data::get::<Orderbook>(store, key.as_str()).await?;
+    tracing::info!("Serving stale orderbook from block {} on {}", orderbook.block, network.name);
+    Ok(CachedOrderbook {
+        age: current_timestamp().saturating_sub(orderbook.timestamp),
+        orderbook,
+        cached: true,
+        stale: true,
+    })
+}
+
+/// Synthetic orderbook for a pair without direct pools
+/// Routes through intermediate tokens with maths::path::routing (same graph as the ETH-worth paths), then simulates each level hop by hop
+async fn multihop(
+    store: &Store,
+    network: Network,
+    shtss: SharedTychoStreamState,
+    atks: &[SrzToken],
+    acps: &[SrzProtocolComponent],
+    params: OrderbookRequestParams,
+) -> Result<MultiHopOrderbook, ApiError> {
+    let (base, quote) = shared::helpers::pair(atks, params.tag.as_str())?;
+    let (addrbase, addrquote, eth) = (base.address.to_lowercase(), quote.address.to_lowercase(), network.eth.to_lowercase());
+    let paths = (
+        maths::path::routing(acps.to_vec(), addrbase.clone(), addrquote.clone()),
+        maths::path::routing(acps.to_vec(), addrquote.clone(), addrbase.clone()),
+        maths::path::routing(acps.to_vec(), addrbase.clone(), eth.clone()),
+        maths::path::routing(acps.to_vec(), addrquote.clone(), eth.clone()),
+    );
+    let (bids_path, asks_path, base_to_eth, quote_to_eth) = match paths {
+        (Ok(a), Ok(b), Ok(c), Ok(d)) => (a, b, c, d),
+        _ => {
+            let msg = format!("Routing failed: couldn't find a multi-hop path between {} and {}", base.symbol, quote.symbol);
+            tracing::error!("{}", msg);
+            return Err(ApiError::RoutingFailed(msg));
+        }
+    };
+    // Snapshot the protosims of every component involved, to release the lock before simulating
+    let mut ptss: Vec<ProtoSimComp> = vec![];
+    let mtx = shtss.read().await;
+    for cp in acps.iter() {
+        let id = cp.id.to_lowercase();
+        let used = [&bids_path.comp_path, &asks_path.comp_path, &base_to_eth.comp_path, &quote_to_eth.comp_path]
+            .iter()
+            .any(|x| x.contains(&id));
+        if used {
+            if let Some(protosim) = mtx.protosims.get(&id) {
+                ptss.push(ProtoSimComp {
+                    component: cp.clone(),
+                    protosim: protosim.clone(),
+                });
             }
-            let srzt0 = srzt0.unwrap();
-            let srzt1 = srzt1.unwrap();
-            let targets = vec![srzt0.clone(), srzt1.clone()];
-            let base_to_eth = maths::path::routing(acps.clone(), srzt0.address.to_string().to_lowercase(), network.eth.to_lowercase());
-            let quote_to_eth = maths::path::routing(acps.clone(), srzt1.address.to_string().to_lowercase(), network.eth.to_lowercase());
-            match (base_to_eth, quote_to_eth) {
-                (Ok(base_to_eth), Ok(quote_to_eth)) => {
-                    // tracing::info!("Path from {} to network.ETH is {:?}", srzt0.symbol, base_to_eth_path);
-                    if targets.len() == 2 {
-                        let mut ptss: Vec<ProtoSimComp> = vec![];
-                        let mut to_eth_ptss: Vec<ProtoSimComp> = vec![];
-                        for cp in acps.clone() {
-                            let cptks = cp.tokens.clone();
-                            if book::matchcp(cptks.clone(), targets.clone()) {
-                                let mtx = shtss.read().await;
-                                match mtx.protosims.get(&cp.id.to_lowercase()) {
-                                    Some(protosim) => {
-                                        ptss.push(ProtoSimComp {
-                                            component: cp.clone(),
-                                            protosim: protosim.clone(),
-                                        });
-                                    }
-                                    None => {
-                                        tracing::error!("matchcp: couldn't find protosim for component {}", cp.id);
-                                    }
-                                }
-                                drop(mtx);
-                            }
-                            if base_to_eth.comp_path.contains(&cp.id.to_lowercase()) || quote_to_eth.comp_path.contains(&cp.id.to_lowercase()) {
-                                let mtx = shtss.read().await;
-                                match mtx.protosims.get(&cp.id.to_lowercase()) {
-                                    Some(protosim) => {
-                                        to_eth_ptss.push(ProtoSimComp {
-                                            component: cp.clone(),
-                                            protosim: protosim.clone(),
-                                        });
-                                    }
-                                    None => {
-                                        tracing::error!("contains: couldn't find protosim for component {}", cp.id);
-                                    }
-                                }
-                                drop(mtx);
-                            }
-                        }
+        }
+    }
+    drop(mtx);
+    let route_bids = shared::route::hops(&ptss, &bids_path.comp_path, &bids_path.token_path);
+    let route_asks = shared::route::hops(&ptss, &asks_path.comp_path, &asks_path.token_path);
+    let (route_bids, route_asks) = match (route_bids, route_asks) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(ApiError::RoutingFailed("Couldn't find protosims for every hop of the route".to_string())),
+    };
+    tracing::debug!("Multi-hop route for {}: bids via {:?} | asks via {:?}", params.tag, bids_path.token_path, asks_path.token_path);
+    // Levels are sized in ETH worth, like regular orderbooks. Quote of 1 token, in ETH
+    let unit_base_ethworth = maths::path::quote(ptss.clone(), atks.to_vec(), base_to_eth.token_path.clone());
+    let unit_quote_ethworth = maths::path::quote(ptss.clone(), atks.to_vec(), quote_to_eth.token_path.clone());
+    let (unit_base_ethworth, unit_quote_ethworth) = match (unit_base_ethworth, unit_quote_ethworth) {
+        (Some(a), Some(b)) => (a, b),
+        _ => {
+            let msg = format!("Couldn't quote {} or {} in ETH to size the multi-hop levels", base.symbol, quote.symbol);
+            tracing::error!("{}", msg);
+            return Err(ApiError::RoutingFailed(msg));
+        }
+    };
+    let mid = shared::route::spot(&ptss, atks, &route_bids).ok_or(ApiError::SimulationFailed("Couldn't compute the spot price along the route".to_string()))?;
+    let bids = shared::route::levels(&ptss, atks, &route_bids, 1. / unit_base_ethworth, true);
+    let asks = shared::route::levels(&ptss, atks, &route_asks, 1. / unit_quote_ethworth, false);
+    if bids.is_empty() && asks.is_empty() {
+        return Err(ApiError::SimulationFailed(format!("Couldn't simulate any multi-hop level for {}", params.tag)));
+    }
+    let block = getters::latest(store, network.clone()).await.unwrap_or_default();
+    Ok(MultiHopOrderbook {
+        tag: format!("{}-{}", addrbase, addrquote),
+        block,
+        timestamp: current_timestamp(),
+        base,
+        quote,
+        mid,
+        route_bids,
+        route_asks,
+        bids,
+        asks,
+    })
+}
 
-                        if ptss.is_empty() {
-                            let tag = format!("{}-{}", srzt0.symbol.to_lowercase(), srzt1.symbol.to_lowercase());
-                            let msg = format!("ProtoSimComp: pair {} requested has 0 associated pools and multi-hop is not enabled yet.", tag);
-                            return wrap(None, Some(msg));
-                        }
+/// Compute the orderbook for a given pair tag, from the shared stream state
+/// Reuse the cached orderbook if still up to date (only for full orderbooks, not single point simulations)
+/// A pair without any direct pool falls back to a multi-hop orderbook, as in /orderbooks and /ws/orderbook
+async fn compute(
+    store: &Store,
+    index: &Index,
+    network: Network,
+    shtss: SharedTychoStreamState,
+    config: EnvAPIConfig,
+    params: OrderbookRequestParams,
+    max_age: Option<u64>,
+) -> Result<OrderbookResponse, ApiError> {
+    let (atks, acps) = load(store, network.clone()).await?;
+    match plan(&network, &atks, &acps, params.tag.as_str()) {
+        Ok(plan) => {
+            let snapshot = snapshot(&shtss, &acps, &plan.ids()).await;
+            solve(store, index, network, config, &atks, &acps, &plan, &snapshot, params, max_age)
+                .await
+                .map(OrderbookResponse::Direct)
+        }
+        Err(ApiError::NoPools(msg)) if fallback(&params) => {
+            tracing::info!("{} Falling back to multi-hop routing.", msg);
+            multihop(store, network, shtss, &atks, &acps, params).await.map(OrderbookResponse::Multihop)
+        }
+        Err(e) => Err(e),
+    }
+}
 
-                        if !single {
-                            if let Some(cache_obk) = shared::helpers::verify_obcache(network.clone(), acps.clone(), params.tag.clone()).await {
-                                return wrap(Some(cache_obk), None);
-                            } else {
-                                tracing::debug!("Orderbook not found in cache: {}", params.tag);
-                            }
-                        }
+/// True if a pair without direct pool is routed through intermediate tokens: full orderbooks only, single point simulations answer no_pools
+/// Pairs whose direct pools have no protosim yet never fall back, solve answers not_initialised for them
+fn fallback(params: &OrderbookRequestParams) -> bool {
+    params.point.is_none()
+}
+
+/// Components needed to compute the orderbook of a pair, resolved before reading the shared state
+struct Plan {
+    base: SrzToken,
+    quote: SrzToken,
+    // Components having base AND quote
+    direct: Vec<String>,
+    // Components on the base and quote paths to ETH
+    to_eth: Vec<String>,
+    base_to_eth: Vec<String>,
+    quote_to_eth: Vec<String>,
+}
+
+impl Plan {
+    /// All components ids whose protosim is needed
+    fn ids(&self) -> HashSet<String> {
+        self.direct.iter().chain(self.to_eth.iter()).cloned().collect()
+    }
+
+    /// Addresses tag of the pair (0xbase-0xquote), whatever the requested tag format (addresses or symbols)
+    fn tag(&self) -> String {
+        format!("{}-{}", self.base.address.to_lowercase(), self.quote.address.to_lowercase())
+    }
+}
+
+/// Load tokens and components from Redis
+async fn load(store: &Store, network: Network) -> Result<(Vec<SrzToken>, Vec<SrzProtocolComponent>), ApiError> {
+    match (getters::tokens(store, network.clone()).await, getters::components_at(store, network.clone()).await) {
+        (Ok(atks), Ok((snapshot, acps))) => {
+            tracing::debug!("Loaded {} components at block {} (snapshot v{})", acps.len(), snapshot.block, snapshot.version);
+            Ok((atks, acps))
+        }
+        (Err(e), _) => {
+            tracing::error!("Couldn't get tokens: {}", e);
+            Err(e.into())
+        }
+        (_, Err(e)) => {
+            tracing::error!("Couldn't get components: {}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// Resolve the pair tokens, the paths to ETH and the matching components of a pair tag
+fn plan(network: &Network, atks: &[SrzToken], acps: &[SrzProtocolComponent], tag: &str) -> Result<Plan, ApiError> {
+    let (srzt0, srzt1) = shared::helpers::pair(atks, tag)?;
+    let targets = vec![srzt0.clone(), srzt1.clone()];
+    let base_to_eth = maths::path::routing(acps.to_vec(), srzt0.address.to_string().to_lowercase(), network.eth.to_lowercase());
+    let quote_to_eth = maths::path::routing(acps.to_vec(), srzt1.address.to_string().to_lowercase(), network.eth.to_lowercase());
+    match (base_to_eth, quote_to_eth) {
+        (Ok(base_to_eth), Ok(quote_to_eth)) => {
+            // tracing::info!("Path from {} to network.ETH is {:?}", srzt0.symbol, base_to_eth_path);
+            let mut direct = vec![];
+            let mut to_eth = vec![];
+            for cp in acps.iter() {
+                let id = cp.id.to_lowercase();
+                if book::matchcp(cp.tokens.clone(), targets.clone()) {
+                    direct.push(id.clone());
+                }
+                if base_to_eth.comp_path.contains(&id) || quote_to_eth.comp_path.contains(&id) {
+                    to_eth.push(id);
+                }
+            }
+            if direct.is_empty() {
+                let tag = format!("{}-{}", srzt0.symbol.to_lowercase(), srzt1.symbol.to_lowercase());
+                let msg = format!("ProtoSimComp: pair {} requested has 0 associated pools.", tag);
+                return Err(ApiError::NoPools(msg));
+            }
+            Ok(Plan {
+                base: srzt0,
+                quote: srzt1,
+                direct,
+                to_eth,
+                base_to_eth: base_to_eth.token_path.clone(),
+                quote_to_eth: quote_to_eth.token_path.clone(),
+            })
+        }
+        _ => {
+            let msg = "Routing failed: couldn't find the path from token to ETH".to_string();
+            tracing::error!("{}", msg);
+            Err(ApiError::RoutingFailed(msg))
+        }
+    }
+}
+
+/// Clone the protosims of the given components, under a single read lock of the shared state
+async fn snapshot(shtss: &SharedTychoStreamState, acps: &[SrzProtocolComponent], ids: &HashSet<String>) -> Vec<ProtoSimComp> {
+    let mut ptss: Vec<ProtoSimComp> = vec![];
+    let mtx = shtss.read().await;
+    for cp in acps.iter() {
+        let id = cp.id.to_lowercase();
+        if !ids.contains(&id) {
+            continue;
+        }
+        match mtx.protosims.get(&id) {
+            Some(protosim) => {
+                ptss.push(ProtoSimComp {
+                    component: cp.clone(),
+                    protosim: protosim.clone(),
+                });
+            }
+            None => {
+                tracing::error!("snapshot: couldn't find protosim for component {}", cp.id);
+            }
+        }
+    }
+    drop(mtx);
+    ptss
+}
+
+/// Simulate the orderbook of a planned pair, with the protosims of a snapshot
+#[allow(clippy::too_many_arguments)]
+async fn solve(
+    store: &Store,
+    index: &Index,
+    network: Network,
+    config: EnvAPIConfig,
+    atks: &[SrzToken],
+    acps: &[SrzProtocolComponent],
+    plan: &Plan,
+    snapshot: &[ProtoSimComp],
+    params: OrderbookRequestParams,
+    max_age: Option<u64>,
+) -> Result<CachedOrderbook, ApiError> {
+    // The SDK, the returned and the cached orderbook only see the resolved addresses tag
+    let params = OrderbookRequestParams { tag: plan.tag(), ..params };
+    let single = params.point.is_some();
+    let max_age = max_age.unwrap_or(config.cache_ttl);
+    let targets = vec![plan.base.clone(), plan.quote.clone()];
+    let ptss = snapshot.iter().filter(|x| plan.direct.contains(&x.component.id.to_lowercase())).cloned().collect::<Vec<ProtoSimComp>>();
+    let to_eth_ptss = snapshot.iter().filter(|x| plan.to_eth.contains(&x.component.id.to_lowercase())).cloned().collect::<Vec<ProtoSimComp>>();
+    if ptss.is_empty() {
+        // The pair has pools, but none of them has a protosim in this replica yet
+        let tag = format!("{}-{}", plan.base.symbol.to_lowercase(), plan.quote.symbol.to_lowercase());
+        let msg = format!("No protosim yet for the {} pools of pair {}", plan.direct.len(), tag);
+        return Err(ApiError::NotInitialised(msg));
+    }
+
+    if !single && max_age > 0 {
+        // Cached under the addresses tag, whatever the requested tag format (addresses or symbols)
+        if let Some(cache_obk) = shared::helpers::verify_obcache(store, network.clone(), acps.to_vec(), params.tag.clone(), max_age).await {
+            let age = current_timestamp().saturating_sub(cache_obk.timestamp);
+            return Ok(CachedOrderbook {
+                orderbook: cache_obk,
+                cached: true,
+                age,
+                stale: false,
+            });
+        } else {
+            tracing::debug!("Orderbook not found in cache: {}", params.tag);
+        }
+    }
 
-                        let unit_base_ethworth = maths::path::quote(to_eth_ptss.clone(), atks.clone(), base_to_eth.token_path.clone());
-                        let unit_quote_ethworth = maths::path::quote(to_eth_ptss.clone(), atks.clone(), quote_to_eth.token_path.clone());
-                        match (unit_base_ethworth, unit_quote_ethworth) {
-                            (Some(unit_base_ethworth), Some(unit_quote_ethworth)) => {
-                                match book::build(
-                                    DefaultOrderbookSolver,
-                                    network.clone(),
-                                    Some(config.tycho_api_key),
-                                    ptss.clone(),
-                                    targets.clone(),
-                                    params.clone(),
-                                    unit_base_ethworth,
-                                    unit_quote_ethworth,
-                                )
-                                .await
-                                {
-                                    Ok(result) => {
-                                        if !single {
-                                            let tag = format!("{}-{}", result.base.address.to_lowercase(), result.quote.address.to_lowercase());
-                                            let key = keys::stream::orderbook(network.name.clone(), tag);
-                                            tracing::info!("Saving orderbook to Redis cache with key: {}", key);
-                                            shared::data::set(key.as_str(), result.clone()).await;
-                                        }
-                                        wrap(Some(result), None)
-                                    }
-                                    Err(e) => {
-                                        let msg = format!("Couldn't build the orderbook: {}", e);
-                                        tracing::error!("{}", msg);
-                                        wrap(None, Some(msg))
-                                    }
-                                }
-                            }
-                            _ => {
-                                let msg = format!("Couldn't find the quote path from {} to ETH", srzt0.symbol);
-                                tracing::error!("{}", msg);
-                                wrap(None, Some(msg))
-                            }
+    let unit_base_ethworth = maths::path::quote(to_eth_ptss.clone(), atks.to_vec(), plan.base_to_eth.clone());
+    let unit_quote_ethworth = maths::path::quote(to_eth_ptss.clone(), atks.to_vec(), plan.quote_to_eth.clone());
+    match (unit_base_ethworth, unit_quote_ethworth) {
+        (Some(unit_base_ethworth), Some(unit_quote_ethworth)) => {
+            match book::build(
+                DefaultOrderbookSolver,
+                network.clone(),
+                Some(config.tycho_api_key.clone()),
+                ptss.clone(),
+                targets.clone(),
+                params.clone(),
+                unit_base_ethworth,
+                unit_quote_ethworth,
+            )
+            .await
+            {
+                Ok(result) => {
+                    if !single {
+                        let tag = format!("{}-{}", result.base.address.to_lowercase(), result.quote.address.to_lowercase());
+                        let key = keys::stream::orderbook(network.name.clone(), tag);
+                        tracing::info!("Saving orderbook to Redis cache with key: {} (ttl {}s)", key, config.cache_ttl);
+                        if shared::data::set_ex(store, key.as_str(), result.clone(), config.cache_ttl).await.is_ok() {
+                            shared::invalidation::track(index, key, result.pools.iter().map(|x| x.id.clone()).collect()).await;
                         }
-                    } else {
-                        let msg = format!(
-                            "Couldn't find the pair of tokens for tag {} - Query param Tag must contain only 2 tokens separated by a dash '-'",
-                            target
-                        );
-                        tracing::error!("{}", msg);
-                        wrap(None, Some(msg))
                     }
+                    Ok(CachedOrderbook {
+                        orderbook: result,
+                        cached: false,
+                        age: 0,
+                        stale: false,
+                    })
                 }
-                _ => {
-                    let msg = "Routing failed: couldn't find the path from token to ETH".to_string();
+                Err(e) => {
+                    let msg = format!("Couldn't build the orderbook: {}", e);
                     tracing::error!("{}", msg);
-                    wrap(None, Some(msg.to_string()))
+                    Err(ApiError::SimulationFailed(msg))
                 }
             }
         }
-        (None, _) => {
-            let msg = "Couldn't get tokens.".to_string();
+        _ => {
+            let msg = format!("Couldn't find the quote path from {} to ETH", plan.base.symbol);
             tracing::error!("{}", msg);
-            wrap(None, Some(msg))
+            Err(ApiError::RoutingFailed(msg))
         }
-        (_, None) => {
-            let msg = "Couldn't get components.".to_string();
+    }
+}
+
+// POST /orderbooks => Simulate several orderbooks at once
+#[utoipa::path(
+    post,
+    path = "/orderbooks",
+    summary = "Orderbooks for a list of pairs",
+    description = "Same as /orderbook for several pairs at once. Tokens, components and protosims are loaded once, books are computed concurrently. Each tag gets either an orderbook or an error. Pairs without direct pool get a multi-hop orderbook, as in /orderbook",
+    request_body = Vec<OrderbookQuery>,
+    responses(
+        (status = 200, description = "Orderbooks and errors, by tag", body = BatchOrderbooks),
+        (status = 400, description = "Empty or too large batch (code: bad_request)", body = APIResponse<String>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised) or storage unavailable (code: storage)", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn orderbooks(
+    headers: HeaderMap,
+    Extension(shtss): Extension<SharedTychoStreamState>,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(index): Extension<Index>,
+    Extension(config): Extension<EnvAPIConfig>,
+    AxumExJson(requests): AxumExJson<Vec<OrderbookQuery>>,
+) -> Response {
+    tracing::info!("👾 API: {} : Batch of {} OrderbookRequestParams", network.name, requests.len());
+    let mtx = shtss.read().await;
+    let initialised = mtx.initialised;
+    drop(mtx);
+    if let Some(e) = prevalidation(&store, network.clone(), headers.clone(), initialised, config.web_api_key.clone()).await {
+        return wrap(None, Some(e));
+    }
+    if requests.is_empty() || requests.len() > MAX_BATCH_ORDERBOOKS {
+        let msg = format!("Batch must contain between 1 and {} orderbook requests, got {}", MAX_BATCH_ORDERBOOKS, requests.len());
+        return wrap(None, Some(ApiError::BadRequest(msg)));
+    }
+    let (atks, acps) = match load(&store, network.clone()).await {
+        Ok(loaded) => loaded,
+        Err(e) => return wrap(None, Some(e)),
+    };
+    let mut result = BatchOrderbooks {
+        orderbooks: HashMap::new(),
+        errors: HashMap::new(),
+    };
+    let mut plans = vec![];
+    let mut multihops = vec![];
+    for query in requests {
+        match plan(&network, &atks, &acps, query.params.tag.as_str()) {
+            Ok(plan) => plans.push((query, plan)),
+            Err(ApiError::NoPools(_)) if fallback(&query.params) => multihops.push(query),
+            Err(e) => {
+                result.errors.insert(query.params.tag.clone(), e);
+            }
+        }
+    }
+    let ids = plans.iter().flat_map(|(_, plan)| plan.ids()).collect::<HashSet<String>>();
+    let snapshot = Arc::new(snapshot(&shtss, &acps, &ids).await);
+    let (atks, acps) = (Arc::new(atks), Arc::new(acps));
+    let mut handles = vec![];
+    for (query, plan) in plans {
+        let (store, index, network, config, atks, acps, snapshot) = (store.clone(), index.clone(), network.clone(), config.clone(), atks.clone(), acps.clone(), snapshot.clone());
+        let tag = query.params.tag.clone();
+        let handle = tokio::spawn(async move {
+            solve(&store, &index, network, config, &atks, &acps, &plan, &snapshot, query.params, query.max_age)
+                .await
+                .map(OrderbookResponse::Direct)
+        });
+        handles.push((tag, handle));
+    }
+    // Pairs without direct pool, routed through intermediate tokens like /orderbook
+    for query in multihops {
+        let (store, network, shtss, atks, acps) = (store.clone(), network.clone(), shtss.clone(), atks.clone(), acps.clone());
+        let tag = query.params.tag.clone();
+        let handle = tokio::spawn(async move { multihop(&store, network, shtss, &atks, &acps, query.params).await.map(OrderbookResponse::Multihop) });
+        handles.push((tag, handle));
+    }
+    for (tag, handle) in handles {
+        match handle.await {
+            Ok(Ok(orderbook)) => {
+                result.orderbooks.insert(tag, orderbook);
+            }
+            Ok(Err(e)) => {
+                result.errors.insert(tag, e);
+            }
+            Err(e) => {
+                result.errors.insert(tag, ApiError::SimulationFailed(format!("Orderbook task failed: {}", e)));
+            }
+        }
+    }
+    tracing::debug!("Batch done: {} orderbooks, {} errors", result.orderbooks.len(), result.errors.len());
+    wrap(Some(result), None)
+}
+
+// GET /quote?sell=0x..&buy=0x..&amount=.. => Quote a single amount
+#[utoipa::path(
+    get,
+    path = "/quote",
+    summary = "Quote a single amount",
+    description = "Amount out, effective price, price impact versus mid and split across pools, for a single amount. Lighter than a full orderbook, computed from the in-memory protosims only",
+    params(QuoteParams),
+    responses(
+        (status = 200, description = "Quote for the amount", body = Quote),
+        (status = 400, description = "Invalid amount (code: bad_request)", body = APIResponse<String>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 404, description = "No pool with both tokens (code: no_pools)", body = APIResponse<String>),
+        (status = 500, description = "Quote simulation failed (code: simulation_failed)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised), including after a restart until the first block: protosims aren't checkpointed", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn quote(
+    headers: HeaderMap,
+    Extension(shtss): Extension<SharedTychoStreamState>,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(config): Extension<EnvAPIConfig>,
+    Query(params): Query<QuoteParams>,
+) -> Response {
+    tracing::info!("👾 API: {} : Quote {} {} => {}", network.name, params.amount, params.sell, params.buy);
+    let mtx = shtss.read().await;
+    let initialised = mtx.initialised;
+    drop(mtx);
+    if let Some(e) = prevalidation(&store, network.clone(), headers.clone(), initialised, config.web_api_key.clone()).await {
+        return wrap(None, Some(e));
+    }
+    if !params.amount.is_finite() || params.amount <= 0. {
+        return wrap(None, Some(ApiError::BadRequest(format!("Amount must be strictly positive, got {}", params.amount))));
+    }
+    let (sell, buy) = (params.sell.to_lowercase(), params.buy.to_lowercase());
+    let mut tokens = None;
+    let mut ptss: Vec<ProtoSimComp> = vec![];
+    let mtx = shtss.read().await;
+    for (id, cp) in mtx.components.iter() {
+        let tsell = cp.tokens.iter().find(|t| t.address.to_string().to_lowercase() == sell);
+        let tbuy = cp.tokens.iter().find(|t| t.address.to_string().to_lowercase() == buy);
+        if let (Some(tsell), Some(tbuy)) = (tsell, tbuy) {
+            if let Some(protosim) = mtx.protosims.get(&id.to_lowercase()) {
+                tokens = Some((tsell.clone(), tbuy.clone()));
+                ptss.push(ProtoSimComp {
+                    component: SrzProtocolComponent::from(cp.clone()),
+                    protosim: protosim.clone(),
+                });
+            }
+        }
+    }
+    drop(mtx);
+    let Some((tsell, tbuy)) = tokens else {
+        let msg = format!("No pool found with both {} and {}", params.sell, params.buy);
+        return wrap(None, Some(ApiError::NoPools(msg)));
+    };
+    let block = getters::latest(&store, network.clone()).await.unwrap_or_default();
+    match shared::quote::quote(&ptss, &tsell, &tbuy, params.amount, block) {
+        Some(result) => wrap(Some(result), None),
+        None => {
+            let msg = format!("Couldn't quote {} {} across {} pools", params.amount, tsell.symbol, ptss.len());
             tracing::error!("{}", msg);
-            wrap(None, Some(msg))
+            wrap(None, Some(ApiError::SimulationFailed(msg)))
+        }
+    }
+}
+
+// GET /feed/tokens => Changes of the token list
+#[utoipa::path(
+    get,
+    path = "/feed/tokens",
+    summary = "Feed of new (and delisted) tokens",
+    description = "Changes of the Tycho token list found by the periodic refresh, oldest first. Resume after a disconnect by passing the id of the last entry read as 'from'",
+    params(FeedParams),
+    responses(
+        (status = 200, description = "Token list changes after 'from'", body = Vec<TokenDiff>),
+        (status = 400, description = "Count out of bounds (code: bad_request)", body = APIResponse<String>),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 503, description = "Storage unavailable (code: storage)", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn token_feed(
+    headers: HeaderMap,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(config): Extension<EnvAPIConfig>,
+    Query(params): Query<FeedParams>,
+) -> Response {
+    tracing::info!("👾 API: GET /feed/tokens on {} network | {:?}", network.name, params);
+    let (allowed, msg) = validate_headers(&headers, config.web_api_key);
+    if !allowed {
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
+    }
+    let count = params.count.unwrap_or(100);
+    if count == 0 || count > MAX_PAGE_LIMIT {
+        return wrap(None, Some(ApiError::BadRequest(format!("Count must be between 1 and {}, got {}", MAX_PAGE_LIMIT, count))));
+    }
+    let key = keys::stream::token_feed(network.name.clone());
+    let from = params.from.unwrap_or("0".to_string());
+    match shared::data::read_token_feed(&store, key.as_str(), from.as_str(), count).await {
+        Ok(entries) => wrap(Some(entries), None),
+        Err(e) => wrap(None, Some(e.into())),
+    }
+}
+
+// GET /events => Server-Sent Events, one per processed block
+#[utoipa::path(
+    get,
+    path = "/events",
+    summary = "Live feed of processed blocks (SSE)",
+    description = "Server-Sent Events stream, emitting one 'block' event per BlockUpdate processed by the stream: block number, updated components, new and removed pairs, and the current stream state. Each transition of the stream state (e.g. Syncing, Error, Down) is also emitted, with the latest block and no component change",
+    responses(
+        (status = 200, description = "Stream of 'block' events", body = BlockEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn events(headers: HeaderMap, Extension(network): Extension<Network>, Extension(events): Extension<broadcast::Sender<BlockEvent>>, Extension(config): Extension<EnvAPIConfig>) -> Response {
+    tracing::info!("👾 API: GET /events on {} network", network.name);
+    let (allowed, msg) = validate_headers(&headers, config.web_api_key);
+    if !allowed {
+        return wrap(None, Some(ApiError::Unauthorized(msg)));
+    }
+    let name = network.name.clone();
+    let feed = futures::stream::unfold(events.subscribe(), move |mut rx| {
+        let name = name.clone();
+        async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((Event::default().event("block").json_data(event), rx)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("SSE subscriber on {} lagging, skipped {} block events", name, skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+    Sse::new(feed).keep_alive(KeepAlive::default()).into_response()
+}
+
+// GET /ws/orderbook?tag=0xt0-0xt1 (or WETH-USDC) => Push the orderbook each time one of its pools is updated
+#[utoipa::path(
+    get,
+    path = "/ws/orderbook",
+    summary = "Live orderbook of a pair (WebSocket)",
+    description = "WebSocket pushing the orderbook of the pair on connection, then after each block updating (or removing) one of its pools. Each message is an APIResponse, carrying an orderbook or an error. An error is pushed once, the orderbook follows when the pair gets pools",
+    params(OrderbookStreamParams),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol, messages are APIResponse<OrderbookResponse>"),
+        (status = 401, description = "Unauthorized: missing or invalid API key header (code: unauthorized)", body = APIResponse<String>),
+        (status = 503, description = "Stream not yet initialised (code: not_initialised)", body = APIResponse<String>)
+    ),
+    tag = (
+        "API"
+    )
+)]
+async fn ws_orderbook(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Extension(shtss): Extension<SharedTychoStreamState>,
+    Extension(network): Extension<Network>,
+    Extension(store): Extension<Store>,
+    Extension(events): Extension<broadcast::Sender<BlockEvent>>,
+    Extension(index): Extension<Index>,
+    Extension(config): Extension<EnvAPIConfig>,
+    Query(params): Query<OrderbookStreamParams>,
+) -> Response {
+    tracing::info!("👾 API: {} : WebSocket orderbook subscription on {}", network.name, params.tag);
+    let mtx = shtss.read().await;
+    let initialised = mtx.initialised;
+    drop(mtx);
+    if let Some(e) = prevalidation(&store, network.clone(), headers.clone(), initialised, config.web_api_key.clone()).await {
+        return wrap(None, Some(e));
+    }
+    // Subscribed before the first orderbook is computed, so that no block is missed in between
+    let receiver = events.subscribe();
+    ws.on_upgrade(move |socket| subscribe(socket, receiver, store, index, network, shtss, config, params.tag))
+}
+
+/// Orderbook WebSocket session, driven by the block events of the stream
+/// Components updated or removed are collected over every block received since the last push (a compute can last several blocks),
+/// and a fresh orderbook is pushed only if one of its pools is among them
+/// Without orderbook, the pair is retried when a component is added with one of its tokens (each block for transient errors), and a same error is pushed once
+#[allow(clippy::too_many_arguments)]
+async fn subscribe(
+    mut socket: WebSocket,
+    mut receiver: broadcast::Receiver<BlockEvent>,
+    store: Store,
+    index: Index,
+    network: Network,
+    shtss: SharedTychoStreamState,
+    config: EnvAPIConfig,
+    tag: String,
+) {
+    let params = OrderbookRequestParams { tag: tag.clone(), point: None };
+    let mut pools: HashSet<String> = HashSet::new();
+    // Token addresses of the pair: without orderbook, only a component added with one of them can give it one
+    let tokens = match getters::tokens(&store, network.clone()).await.ok().and_then(|atks| shared::helpers::pair(&atks, tag.as_str()).ok()) {
+        Some((base, quote)) => vec![base.address.to_lowercase(), quote.address.to_lowercase()],
+        None => vec![],
+    };
+    // Error of the last compute, the same error isn't pushed twice in a row
+    let mut failed: Option<ApiError> = None;
+    // First orderbook pushed right away
+    let mut stale = true;
+    loop {
+        if stale {
+            stale = false;
+            let response = match compute(&store, &index, network.clone(), shtss.clone(), config.clone(), params.clone(), None).await {
+                Ok(result) => {
+                    failed = None;
+                    pools = match &result {
+                        OrderbookResponse::Direct(x) => x.orderbook.pools.iter().map(|x| x.id.to_lowercase()).collect(),
+                        OrderbookResponse::Multihop(x) => x.route_bids.iter().chain(x.route_asks.iter()).map(|x| x.component.to_lowercase()).collect(),
+                    };
+                    APIResponse {
+                        success: true,
+                        error: String::default(),
+                        code: None,
+                        data: Some(json!(result)),
+                        ts: current_timestamp(),
+                    }
+                }
+                Err(e) => {
+                    pools.clear();
+                    let repeated = failed.as_ref().is_some_and(|x| x.code() == e.code() && x.message() == e.message());
+                    let response = APIResponse {
+                        success: false,
+                        error: e.message(),
+                        code: Some(e.code().to_string()),
+                        data: None,
+                        ts: current_timestamp(),
+                    };
+                    failed = Some(e);
+                    if repeated {
+                        continue;
+                    }
+                    response
+                }
+            };
+            let payload = serde_json::to_string(&response).unwrap_or_default();
+            if socket.send(Message::Text(payload.into())).await.is_err() {
+                tracing::debug!("WebSocket orderbook subscription on {}: client gone", tag);
+                break;
+            }
+        }
+        tokio::select! {
+            incoming = socket.recv() => {
+                match incoming {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        tracing::debug!("WebSocket orderbook subscription on {} closed", tag);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            event = receiver.recv() => {
+                // Events queued while the previous orderbook was computed are drained at once
+                let mut next = event;
+                loop {
+                    match next {
+                        Ok(event) => {
+                            stale |= match failed.as_ref() {
+                                // Not synced yet or storage down: transient, retried on the next block
+                                Some(ApiError::NotInitialised(_)) | Some(ApiError::Storage(_)) => true,
+                                // No orderbook for the pair: retried only once a component is added with one of its tokens
+                                Some(_) => event.added.iter().any(|x| x.tokens.iter().any(|t| tokens.contains(&t.address.to_lowercase()))),
+                                None => event.updated.iter().chain(event.removed.iter()).any(|x| pools.contains(x)),
+                            };
+                        }
+                        Err(RecvError::Lagged(skipped)) => {
+                            tracing::warn!("WebSocket orderbook subscription on {} lagging, skipped {} block events", tag, skipped);
+                            stale = true;
+                        }
+                        Err(RecvError::Closed) => {
+                            tracing::debug!("WebSocket orderbook subscription on {}: block events closed", tag);
+                            return;
+                        }
+                    }
+                    next = match receiver.try_recv() {
+                        Ok(event) => Ok(event),
+                        Err(broadcast::error::TryRecvError::Lagged(skipped)) => Err(RecvError::Lagged(skipped)),
+                        Err(_) => break,
+                    };
+                }
+            }
         }
     }
 }
 
-pub async fn start(nets: Vec<Network>, shared: crate::Cache, config: EnvAPIConfig) {
+pub async fn start(nets: Vec<Network>, shared: crate::Cache, store: Store, feeds: crate::Events, supervisors: Supervisors, config: EnvAPIConfig) {
     let port = config.api_port.parse::<u16>().unwrap_or(42042);
     let names = nets.clone().iter().map(|n| n.name.clone()).collect::<Vec<String>>();
     tracing::info!("👾 Launching API for '{:?}' networks | 🧪 Testing mode: {:?} | Port: {}", names, config.testing, port);
@@ -483,7 +1326,15 @@ pub async fn start(nets: Vec<Network>, shared: crate::Cache, config: EnvAPIConfi
         .route("/version", get(version))
         .route("/networks", get(networks))
         .layer(Extension(config.clone()))
-        .layer(Extension(nets.clone()));
+        .layer(Extension(
+            nets.iter()
+                .map(|network| NetworkConfig {
+                    network: network.clone(),
+                    filters: StreamFilters::from_env(network.name.as_str()),
+                })
+                .collect::<Vec<NetworkConfig>>(),
+        ))
+        .layer(Extension(store.clone()));
 
     // --- Network router ---
     for network in nets.clone().iter() {
@@ -492,16 +1343,33 @@ pub async fn start(nets: Vec<Network>, shared: crate::Cache, config: EnvAPIConfi
             let map = shared.read().await;
             map.get(&network.name).cloned().expect("Missing state for network")
         };
+        let feed = feeds.get(&network.name).cloned().expect("Missing events channel for network");
+        let supervisor = supervisors.get(&network.name).cloned().expect("Missing supervisor for network");
+        // Orderbooks cached by this replica, dropped as soon as the stream publishes an update of one of their pools
+        let index = Index::default();
+        tokio::spawn(shared::invalidation::listen(store.clone(), network.clone(), index.clone()));
         let netr = Router::new()
             // Network-specific routes (e.g. components, pairs, etc.)
             .route("/status", get(status))
+            .route("/stream", get(stream))
             .route("/tokens", get(tokens))
+            .route("/tokens/{address_or_symbol}", get(token))
             .route("/components", get(components))
+            .route("/components/{id}", get(component))
             .route("/pairs", get(pairs))
             .route("/orderbook", post(orderbook))
+            .route("/orderbooks", post(orderbooks))
+            .route("/quote", get(quote))
             .route("/execute", post(execute))
+            .route("/ws/orderbook", get(ws_orderbook))
+            .route("/events", get(events))
+            .route("/feed/tokens", get(token_feed))
             .layer(Extension(network.clone()))
             .layer(Extension(state))
+            .layer(Extension(feed))
+            .layer(Extension(supervisor))
+            .layer(Extension(index))
+            .layer(Extension(store.clone()))
             .layer(Extension(config.clone()));
         // Nest each network router under its prefix
         main = main.nest(&prefix, netr);
@@ -0,0 +1,174 @@
+use std::sync::OnceLock;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// First byte of the header of encoded values. Never the first byte of a JSON document (nor valid UTF-8), so values without header are read as plain JSON
+pub static MAGIC: u8 = 0xB7;
+
+/// Bit of the second header byte set when the value is zstd-compressed, the other bits holding the format
+pub static ZSTD_FLAG: u8 = 0x80;
+
+/// Zstd compression level, 3 being the zstd default (fast, good ratio for JSON-like data)
+pub static ZSTD_LEVEL: i32 = 3;
+
+/// Serialization format of the values written to the store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json = 1,
+    MsgPack = 2,
+    // Benchmark only, not selectable with CODEC: bincode is not self-describing,
+    // stored types relying on serde attributes such as flatten or skip_serializing_if (e.g. CachedOrderbook, NetworkConfig) can't be read back with it
+    Bincode = 3,
+}
+
+/// Codec used to encode values: a format, optionally compressed with zstd
+/// Encoded values are prefixed with a 2 bytes header [MAGIC, format | zstd flag], except uncompressed JSON which is written as is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Codec {
+    pub format: Format,
+    pub zstd: bool,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec { format: Format::Json, zstd: false }
+    }
+}
+
+impl Codec {
+    /// Build the codec from the CODEC ('json' (default) or 'msgpack') and CODEC_ZSTD ('true' to compress) env variables
+    pub fn from_env() -> Self {
+        let format = match std::env::var("CODEC").unwrap_or_default().to_lowercase().as_str() {
+            "msgpack" => Format::MsgPack,
+            "bincode" => {
+                tracing::error!("📕 CODEC=bincode can't read back every stored type (flatten, skip_serializing_if), using json");
+                Format::Json
+            }
+            _ => Format::Json,
+        };
+        let zstd = std::env::var("CODEC_ZSTD").map(|x| x == "true" || x == "1").unwrap_or(false);
+        Codec { format, zstd }
+    }
+
+    /// Encode a value, with the header if needed
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        let body = match self.format {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string())?,
+            Format::MsgPack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string())?,
+            Format::Bincode => bincode::serialize(value).map_err(|e| e.to_string())?,
+        };
+        if self.format == Format::Json && !self.zstd {
+            return Ok(body);
+        }
+        let body = if self.zstd {
+            zstd::encode_all(body.as_slice(), ZSTD_LEVEL).map_err(|e| e.to_string())?
+        } else {
+            body
+        };
+        let flags = self.format as u8 | if self.zstd { ZSTD_FLAG } else { 0 };
+        let mut encoded = Vec::with_capacity(body.len() + 2);
+        encoded.push(MAGIC);
+        encoded.push(flags);
+        encoded.extend_from_slice(body.as_slice());
+        Ok(encoded)
+    }
+
+    /// Decode a value written with any codec, the header telling how it was encoded (plain JSON if there is none)
+    pub fn decode<T: DeserializeOwned>(raw: &[u8]) -> Result<T, String> {
+        if raw.len() < 2 || raw[0] != MAGIC {
+            return serde_json::from_slice(raw).map_err(|e| e.to_string());
+        }
+        let flags = raw[1];
+        let body = if flags & ZSTD_FLAG != 0 {
+            zstd::decode_all(&raw[2..]).map_err(|e| e.to_string())?
+        } else {
+            raw[2..].to_vec()
+        };
+        match flags & !ZSTD_FLAG {
+            1 => serde_json::from_slice(body.as_slice()).map_err(|e| e.to_string()),
+            2 => rmp_serde::from_slice(body.as_slice()).map_err(|e| e.to_string()),
+            3 => bincode::deserialize(body.as_slice()).map_err(|e| e.to_string()),
+            other => Err(format!("Unknown codec format {}", other)),
+        }
+    }
+}
+
+/// Codec used by the data module, read once from the env
+pub fn current() -> Codec {
+    static CODEC: OnceLock<Codec> = OnceLock::new();
+    *CODEC.get_or_init(|| {
+        let codec = Codec::from_env();
+        tracing::info!("📕 Store codec: {:?}", codec);
+        codec
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{Codec, Format, MAGIC, ZSTD_FLAG};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Inner {
+        id: String,
+        block: u64,
+    }
+
+    // Same serde attributes as the stored types (CachedOrderbook, NetworkConfig, Status)
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Value {
+        #[serde(flatten)]
+        inner: Inner,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        items: Vec<f64>,
+    }
+
+    fn value() -> Value {
+        Value {
+            inner: Inner {
+                id: "0xabc".to_string(),
+                block: 22051447,
+            },
+            note: None,
+            items: vec![1.5, 2.25, 3.],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for format in [Format::Json, Format::MsgPack] {
+            for zstd in [false, true] {
+                let codec = Codec { format, zstd };
+                let raw = codec.encode(&value()).expect("encode");
+                assert_eq!(Codec::decode::<Value>(raw.as_slice()).expect("decode"), value(), "{:?}", codec);
+            }
+        }
+    }
+
+    #[test]
+    fn header() {
+        // Plain JSON is written as is, every other codec with the header
+        let raw = Codec::default().encode(&value()).unwrap();
+        assert_eq!(raw, serde_json::to_vec(&value()).unwrap());
+        let raw = Codec { format: Format::MsgPack, zstd: false }.encode(&value()).unwrap();
+        assert_eq!(raw[..2], [MAGIC, Format::MsgPack as u8]);
+        let raw = Codec { format: Format::Json, zstd: true }.encode(&value()).unwrap();
+        assert_eq!(raw[..2], [MAGIC, Format::Json as u8 | ZSTD_FLAG]);
+    }
+
+    #[test]
+    fn legacy_json() {
+        // Values written before the codec existed, plain JSON strings
+        let raw = serde_json::to_string(&value()).unwrap();
+        assert_eq!(Codec::decode::<Value>(raw.as_bytes()).unwrap(), value());
+        assert_eq!(Codec::decode::<u64>(b"42").unwrap(), 42);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Codec::decode::<Value>(&[MAGIC, 9, 1, 2]).is_err());
+        assert!(Codec::decode::<Value>(&[MAGIC, Format::Json as u8 | ZSTD_FLAG, 1, 2]).is_err());
+    }
+}
@@ -1,120 +1,196 @@
 #![allow(unused)] // silence unused warnings while exploring (to comment out)
 
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{error::Error, time::Duration};
-use tokio::time::sleep;
-
-use redis::{
-    aio::MultiplexedConnection,
-    from_redis_value,
-    streams::{StreamRangeReply, StreamReadOptions, StreamReadReply},
-    AsyncCommands, Client, RedisError,
-};
+use tycho_orderbook::{data::fmt::SrzToken, utils::misc::current_timestamp};
 
-use crate::types::StreamState;
+use crate::{
+    codec::{self, Codec},
+    misc::r#static::{JOURNAL_MAXLEN, SCHEMA_VERSION, TOKEN_FEED_MAXLEN},
+    store::{Store, StoreError, WriteOp},
+    types::{BlockEvent, Checkpoint, ComponentEntry, JournalEntry, Snapshot, StreamState, TokenDiff},
+};
 
 pub mod keys {
+    use std::sync::OnceLock;
+
+    use crate::misc::r#static::{KEY_PREFIX, SCHEMA_VERSION};
+
+    /// Prefix of every key, KEY_PREFIX env variable (to share a Redis instance between deployments), read once
+    pub fn prefix() -> &'static str {
+        static PREFIX: OnceLock<String> = OnceLock::new();
+        PREFIX.get_or_init(|| std::env::var("KEY_PREFIX").ok().filter(|x| !x.is_empty()).unwrap_or(KEY_PREFIX.to_string()).to_lowercase())
+    }
+
+    /// Namespace of the keys of the current schema: <prefix>:v<SCHEMA_VERSION>
+    pub fn namespace() -> String {
+        format!("{}:v{}", prefix(), SCHEMA_VERSION)
+    }
+
+    // <prefix>:schema => u32, version of the keys written under the prefix
+    pub fn schema() -> String {
+        format!("{}:schema", prefix())
+    }
 
     pub mod stream {
+        use super::namespace;
 
-        // stream:status:<network> => SyncState
+        // <namespace>:stream:status:<network> => SyncState
         pub fn status(network: String) -> String {
-            format!("stream:status:{}", network.to_lowercase())
+            format!("{}:stream:status:{}", namespace(), network.to_lowercase())
         }
 
-        // stream:latest:<network> => u64
+        // <namespace>:stream:latest:<network> => u64
         pub fn latest(network: String) -> String {
-            format!("stream:latest:{}", network.to_lowercase())
+            format!("{}:stream:latest:{}", namespace(), network.to_lowercase())
         }
 
-        // stream:latest:<network> => u64
+        // <namespace>:stream:updated:<network> => components ids updated at the latest block
         pub fn updated(network: String) -> String {
-            format!("stream:updated:{}", network.to_lowercase())
+            format!("{}:stream:updated:{}", namespace(), network.to_lowercase())
         }
 
-        // stream:tokens:<network> => array of tokens
+        // <namespace>:stream:tokens:<network> => array of tokens
         pub fn tokens(network: String) -> String {
-            format!("stream:tokens:{}", network.to_lowercase())
+            format!("{}:stream:tokens:{}", namespace(), network.to_lowercase())
         }
 
         // Get one orderbook via tag
         pub fn orderbook(network: String, tag: String) -> String {
-            format!("stream:orderbook:{}:{}", network.to_lowercase(), tag.to_lowercase())
+            format!("{}:stream:orderbook:{}:{}", namespace(), network.to_lowercase(), tag.to_lowercase())
+        }
+
+        // <namespace>:stream:journal:<network> => Redis stream of JournalEntry, one per block
+        pub fn journal(network: String) -> String {
+            format!("{}:stream:journal:{}", namespace(), network.to_lowercase())
         }
 
-        // stream:component:id => one component
-        pub fn component(network: String, id: String) -> String {
-            format!("stream:{}:component:{}", network, id.to_lowercase())
+        // <namespace>:stream:invalidate:<network> => pub/sub channel of Invalidation, one message per block
+        pub fn invalidations(network: String) -> String {
+            format!("{}:stream:invalidate:{}", namespace(), network.to_lowercase())
         }
 
-        // stream:components
+        // <namespace>:stream:components:<network> => hash of ComponentEntry, by lowercased component id
         pub fn components(network: String) -> String {
-            format!("stream:components:{}", network.to_lowercase())
+            format!("{}:stream:components:{}", namespace(), network.to_lowercase())
+        }
+
+        // <namespace>:stream:checkpoint:<network> => Checkpoint, tokens and components restored at launch
+        pub fn checkpoint(network: String) -> String {
+            format!("{}:stream:checkpoint:{}", namespace(), network.to_lowercase())
+        }
+
+        // <namespace>:stream:tokens:feed:<network> => Redis stream of TokenDiff, one per change of the token list
+        pub fn token_feed(network: String) -> String {
+            format!("{}:stream:tokens:feed:{}", namespace(), network.to_lowercase())
+        }
+
+        // <namespace>:stream:restart:<network> => RestartInfo
+        pub fn restart(network: String) -> String {
+            format!("{}:stream:restart:{}", namespace(), network.to_lowercase())
+        }
+
+        // <namespace>:stream:snapshot:<network> => u64 (plain integer), incremented by each block transaction
+        pub fn snapshot(network: String) -> String {
+            format!("{}:stream:snapshot:{}", namespace(), network.to_lowercase())
         }
     }
 }
 
-pub async fn ping() {
-    let co = connect().await;
-    match co {
-        Ok(mut co) => {
-            let pong: redis::RedisResult<String> = redis::cmd("PING").query_async(&mut co).await;
-            match pong {
-                Ok(pong) => {
-                    tracing::debug!("📕 Redis Ping Good");
-                }
-                Err(e) => {
-                    panic!("Redis PING Error: {}", e);
-                }
-            }
+/// Check the store is reachable at launch
+pub async fn ping(store: &Store) {
+    let health = store.status().await;
+    if health.connected {
+        tracing::debug!("📕 {} store ping good ({} ms)", health.backend, health.latency_ms);
+    } else {
+        tracing::error!("📕 {} store PING error: {:?}. Carrying on, the connection will be retried on use", health.backend, health.last_error);
+    }
+}
+
+/// Check the schema version of the keys under the prefix, migrating them if they were written by an older version
+/// Refuse to run (Err) against keys written by a newer version, that this version can't read
+/// Keys outside of the prefix are only deleted if drop_legacy is set (DROP_LEGACY_KEYS)
+pub async fn schema(store: &Store, drop_legacy: bool) -> Result<(), String> {
+    let key = keys::schema();
+    let version = match get::<u32>(store, key.as_str()).await {
+        Ok(version) => Some(version),
+        // Keys written before the schema version existed, or a fresh store
+        Err(StoreError::NotFound(_)) => None,
+        Err(err) => return Err(format!("Failed to read the store schema version: {}", err)),
+    };
+    match version {
+        Some(version) if version == SCHEMA_VERSION => {
+            tracing::debug!("📕 Store schema v{} under prefix '{}'", version, keys::prefix());
+            Ok(())
         }
-        Err(e) => {
-            panic!("Redis PING Error: {}", e);
+        Some(version) if version > SCHEMA_VERSION => Err(format!(
+            "Store schema v{} under prefix '{}' is newer than the one supported (v{}). Use another KEY_PREFIX or upgrade",
+            version,
+            keys::prefix(),
+            SCHEMA_VERSION
+        )),
+        _ => {
+            // Keys written before the schema version existed are considered v1
+            let from = version.unwrap_or(1);
+            for version in from..SCHEMA_VERSION {
+                migrate(store, version, drop_legacy)
+                    .await
+                    .map_err(|err| format!("Migration from schema v{} failed: {}", version, err))?;
+            }
+            set(store, key.as_str(), SCHEMA_VERSION)
+                .await
+                .map_err(|err| format!("Failed to write the store schema version: {}", err))?;
+            tracing::info!("📕 Store schema set to v{} under prefix '{}' (was v{})", SCHEMA_VERSION, keys::prefix(), from);
+            Ok(())
         }
     }
 }
 
-/// Connect to Redis
-pub async fn connect() -> Result<MultiplexedConnection, RedisError> {
-    let endpoint = std::env::var("REDIS_HOST");
-    let endpoint = match endpoint {
-        Ok(endpoint) => endpoint,
-        Err(_) => "127.0.0.1:42777".to_string(),
-    };
-    let endpoint = format!("redis://{}", endpoint);
-    // log::info!("Redis endpoint: {}", endpoint);
-    let client = Client::open(endpoint);
-    match client {
-        Ok(client) => client.get_multiplexed_tokio_connection().await,
-        Err(e) => {
-            tracing::error!("Redis Client Error: {}", e);
-            Err(e)
+/// Migrate the keys of a schema version to the next one
+async fn migrate(store: &Store, version: u32, drop_legacy: bool) -> Result<(), StoreError> {
+    match version {
+        1 => {
+            // v1 keys were unprefixed ('stream:*'), with the components as a single JSON array and one key per component
+            // Nothing to copy, everything under the new namespace is rebuilt by the stream at launch
+            // They may still be used by another deployment (e.g. prod still on v1 sharing the instance), so they are only deleted on request
+            let legacy = store.scan("stream:").await?;
+            if !drop_legacy {
+                tracing::info!("📕 Migration v1 => v2: keeping {} unprefixed keys, set DROP_LEGACY_KEYS=true to delete them", legacy.len());
+                return Ok(());
+            }
+            tracing::info!("📕 Migration v1 => v2: dropping {} unprefixed keys", legacy.len());
+            for key in legacy {
+                store.delete(key.as_str()).await?;
+            }
+        }
+        _ => {
+            tracing::debug!("📕 No migration needed from schema v{}", version);
         }
     }
+    Ok(())
 }
 
 /// Get the status of the Redis db for a given network
-pub async fn status(key: String) -> StreamState {
-    let status = get::<u128>(key.as_str()).await;
+pub async fn status(store: &Store, key: String) -> StreamState {
+    let status = get::<u128>(store, key.as_str()).await;
     match status {
-        Some(status) => match status {
+        Ok(status) => match status {
             1 => StreamState::Down,
             2 => StreamState::Launching,
             3 => StreamState::Syncing,
             4 => StreamState::Running,
             _ => StreamState::Error,
         },
-        None => StreamState::Error,
+        Err(_) => StreamState::Error,
     }
 }
 
 /// Infinite waiting for the status 'Running' for a given network
-pub async fn wstatus(key: String, object: String) {
+pub async fn wstatus(store: &Store, key: String, object: String) {
     let time = std::time::SystemTime::now();
     tracing::debug!("Waiting Redis Synchro");
     loop {
         tokio::time::sleep(std::time::Duration::from_millis(5000)).await;
-        let status = status(key.clone()).await;
+        let status = status(store, key.clone()).await;
         tracing::debug!("Waiting for '{object}'. Current status: {:?}", status);
         if let StreamState::Running = status {
             let elasped = time.elapsed().unwrap().as_millis();
@@ -124,78 +200,277 @@ pub async fn wstatus(key: String, object: String) {
     }
 }
 
-/// Delete a JSON object from Redis
-pub async fn delete(key: &str) {
-    let co = connect().await;
-    match co {
-        Ok(mut co) => {
-            let deletion: redis::RedisResult<()> = redis::cmd("DEL").arg(key).query_async(&mut co).await;
-            if let Err(err) = deletion {
-                tracing::error!("Failed to delete JSON object with key '{}': {}", key, err);
+/// Encode an object with the configured codec
+fn encode<T: Serialize>(key: &str, data: &T) -> Result<Vec<u8>, StoreError> {
+    codec::current().encode(data).map_err(|err| {
+        tracing::error!("📕 Failed to serialize object for '{}': {}", key, err);
+        StoreError::Encode(err)
+    })
+}
+
+/// Decode an object written with any codec
+fn decode<T: DeserializeOwned>(key: &str, raw: &[u8]) -> Result<T, StoreError> {
+    Codec::decode(raw).map_err(|err| {
+        tracing::error!("📕 Failed to deserialize object from '{}': {}", key, err);
+        StoreError::Decode(format!("{}: {}", key, err))
+    })
+}
+
+/// Delete an object from the store
+pub async fn delete(store: &Store, key: &str) -> Result<(), StoreError> {
+    store.delete(key).await
+}
+
+/// Save an object to the store
+pub async fn set<T: Serialize>(store: &Store, key: &str, data: T) -> Result<(), StoreError> {
+    store.set(key, encode(key, &data)?).await
+}
+
+/// Save an object to the store, expiring after ttl seconds
+pub async fn set_ex<T: Serialize>(store: &Store, key: &str, data: T, ttl: u64) -> Result<(), StoreError> {
+    store.set_ex(key, encode(key, &data)?, ttl).await
+}
+
+/// Save several objects to the store, in a single round trip. Nothing is written if one of them can't be encoded
+pub async fn mset<T: Serialize>(store: &Store, entries: Vec<(String, T)>) -> Result<(), StoreError> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut raws = vec![];
+    for (key, data) in entries.iter() {
+        raws.push((key.clone(), encode(key, data)?));
+    }
+    store.mset(raws).await
+}
+
+/// Get an object from the store
+pub async fn get<T: Serialize + DeserializeOwned>(store: &Store, key: &str) -> Result<T, StoreError> {
+    match store.get(key).await? {
+        Some(value) => decode(key, &value),
+        None => Err(StoreError::NotFound(key.to_string())),
+    }
+}
+
+/// Save several objects as fields of a hash, in a single round trip. Nothing is written if one of them can't be encoded
+pub async fn hset<T: Serialize>(store: &Store, key: &str, entries: Vec<(String, T)>) -> Result<(), StoreError> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut raws = vec![];
+    for (field, data) in entries.iter() {
+        raws.push((field.clone(), encode(key, data)?));
+    }
+    store.hset(key, raws).await
+}
+
+/// Get several objects from the fields of a hash, missing fields are skipped
+pub async fn hmget<T: Serialize + DeserializeOwned>(store: &Store, key: &str, fields: Vec<String>) -> Result<Vec<T>, StoreError> {
+    let values = store.hmget(key, fields).await?;
+    values.into_iter().flatten().map(|value| decode(key, &value)).collect()
+}
+
+/// Get one object from a field of a hash
+pub async fn hget<T: Serialize + DeserializeOwned>(store: &Store, key: &str, field: &str) -> Result<T, StoreError> {
+    match hmget::<T>(store, key, vec![field.to_string()]).await?.pop() {
+        Some(value) => Ok(value),
+        None => Err(StoreError::NotFound(format!("{} (field {})", key, field))),
+    }
+}
+
+/// Delete several fields of a hash
+pub async fn hdel(store: &Store, key: &str, fields: Vec<String>) -> Result<(), StoreError> {
+    store.hdel(key, fields).await
+}
+
+/// Get all the objects of a hash, NotFound if the hash is empty (never written)
+pub async fn hvals<T: Serialize + DeserializeOwned>(store: &Store, key: &str) -> Result<Vec<T>, StoreError> {
+    let values = store.hvals(key).await?;
+    if values.is_empty() {
+        return Err(StoreError::NotFound(key.to_string()));
+    }
+    values.iter().map(|value| decode(key, value)).collect()
+}
+
+/// Writes of one block, applied at once by commit
+/// The first value that can't be encoded is kept and returned by commit, which then writes nothing
+#[derive(Default)]
+pub struct Transaction {
+    ops: Vec<WriteOp>,
+    error: Option<StoreError>,
+}
+
+impl Transaction {
+    /// Save an object
+    pub fn set<T: Serialize>(&mut self, key: &str, data: T) -> &mut Self {
+        match encode(key, &data) {
+            Ok(raw) => self.ops.push(WriteOp::Set(key.to_string(), raw)),
+            Err(err) => {
+                self.error.get_or_insert(err);
             }
         }
-        Err(e) => {
-            tracing::error!("Redis connection error: {}", e);
-        }
+        self
     }
-}
 
-/// Save a JSON object to Redis
-pub async fn set<T: Serialize>(key: &str, data: T) {
-    let data = serde_json::to_string(&data);
-    match data {
-        Ok(data) => {
-            let co = connect().await;
-            // let client = Client::open("redis://redis/");
-            match co {
-                Ok(mut co) => {
-                    let result: redis::RedisResult<()> = redis::cmd("SET").arg(key).arg(data.clone()).query_async(&mut co).await;
-                    if let Err(err) = result {
-                        tracing::error!("📕 Failed to set value for key '{}': {}", key, err);
-                    }
-                }
+    /// Delete an object, or a whole hash
+    pub fn delete(&mut self, key: &str) -> &mut Self {
+        self.ops.push(WriteOp::Delete(key.to_string()));
+        self
+    }
 
-                Err(e) => {
-                    tracing::error!("📕 Redis connection error: {}", e);
+    /// Save several objects as fields of a hash
+    pub fn hset<T: Serialize>(&mut self, key: &str, entries: Vec<(String, T)>) -> &mut Self {
+        let mut raws = vec![];
+        for (field, data) in entries.iter() {
+            match encode(key, data) {
+                Ok(raw) => raws.push((field.clone(), raw)),
+                Err(err) => {
+                    self.error.get_or_insert(err);
+                    return self;
                 }
             }
         }
-        Err(err) => {
-            tracing::error!("📕 Failed to serialize JSON object: {}", err);
-        }
+        self.ops.push(WriteOp::HSet(key.to_string(), raws));
+        self
+    }
+
+    /// Delete several fields of a hash
+    pub fn hdel(&mut self, key: &str, fields: Vec<String>) -> &mut Self {
+        self.ops.push(WriteOp::HDel(key.to_string(), fields));
+        self
+    }
+
+    /// Increment an integer key, stored as a plain integer (readable with get::<u64> whatever the codec)
+    pub fn incr(&mut self, key: &str) -> &mut Self {
+        self.ops.push(WriteOp::Incr(key.to_string()));
+        self
     }
 }
 
-/// Get a JSON object from Redis
-pub async fn get<T: Serialize + DeserializeOwned>(key: &str) -> Option<T> {
-    let time = std::time::SystemTime::now();
-    let co = connect().await;
-    match co {
-        Ok(mut co) => {
-            let result: redis::RedisResult<String> = redis::cmd("GET").arg(key).query_async(&mut co).await;
-            match result {
-                Ok(value) => {
-                    let elasped = time.elapsed().unwrap().as_millis();
-                    match serde_json::from_str(&value) {
-                        Ok(value) => {
-                            // log::info!("📕 Get succeeded for key '{}'. Elapsed: {}ms", key, elasped);
-                            Some(value)
-                        }
-                        Err(err) => {
-                            tracing::error!("📕 Failed to deserialize JSON object: {}", err);
-                            None
-                        }
-                    }
-                }
-                Err(err) => {
-                    // log::error!("📕 Failed to get value for key '{}': {}", key, err);
-                    None
-                }
+/// Apply the writes of a transaction atomically
+pub async fn commit(store: &Store, tx: Transaction) -> Result<(), StoreError> {
+    if let Some(err) = tx.error {
+        return Err(err);
+    }
+    store.transaction(tx.ops).await
+}
+
+/// Writes of one block of a network, bumping its snapshot version: latest block, stream status, components updated at this block
+/// Callers add their own writes (components hash) to the returned transaction before committing it
+pub fn block(network: String, block: u64, status: StreamState, updated: Vec<String>) -> Transaction {
+    let mut tx = Transaction::default();
+    tx.set(keys::stream::latest(network.clone()).as_str(), block)
+        .set(keys::stream::status(network.clone()).as_str(), status as u128)
+        .set(keys::stream::updated(network.clone()).as_str(), updated)
+        .incr(keys::stream::snapshot(network).as_str());
+    tx
+}
+
+/// Checkpoint the tokens and the components (as stored at the latest transaction) of a network, returning the number of components
+pub async fn checkpoint(store: &Store, network: String, block: u64, tokens: Vec<SrzToken>) -> Result<usize, StoreError> {
+    let key = keys::stream::components(network.clone());
+    let components = hvals::<ComponentEntry>(store, key.as_str()).await?;
+    let count = components.len();
+    let checkpoint = Checkpoint {
+        block,
+        timestamp: current_timestamp(),
+        tokens,
+        components,
+    };
+    set(store, keys::stream::checkpoint(network).as_str(), checkpoint).await?;
+    Ok(count)
+}
+
+/// Writes restoring a checkpoint as the latest data of a network, status 'Launching' until the stream replaces them with live data
+pub fn restore(network: String, checkpoint: Checkpoint) -> Transaction {
+    let key = keys::stream::components(network.clone());
+    let entries = checkpoint.components.into_iter().map(|x| (x.component.id.to_lowercase(), x)).collect::<Vec<_>>();
+    let mut tx = block(network.clone(), checkpoint.block, StreamState::Launching, vec![]);
+    tx.set(keys::stream::tokens(network).as_str(), checkpoint.tokens).delete(key.as_str()).hset(key.as_str(), entries);
+    tx
+}
+
+/// Read the per-block keys of a network at once, along with its components if asked, all tied to the same snapshot version
+/// NotFound until the stream commits its first transaction
+pub async fn snapshot(store: &Store, network: String, components: bool) -> Result<(Snapshot, Vec<ComponentEntry>), StoreError> {
+    let names = vec![
+        keys::stream::snapshot(network.clone()),
+        keys::stream::latest(network.clone()),
+        keys::stream::status(network.clone()),
+        keys::stream::updated(network.clone()),
+    ];
+    let hash = keys::stream::components(network.clone());
+    let (values, fields) = store.snapshot(names.clone(), if components { Some(hash.as_str()) } else { None }).await?;
+    let value = |x: usize| match values.get(x).cloned().flatten() {
+        Some(raw) => Ok(raw),
+        None => Err(StoreError::NotFound(names[x].clone())),
+    };
+    let snapshot = Snapshot {
+        version: decode(names[0].as_str(), &value(0)?)?,
+        block: decode(names[1].as_str(), &value(1)?)?,
+        stream: decode(names[2].as_str(), &value(2)?)?,
+        updated: match value(3) {
+            Ok(raw) => decode(names[3].as_str(), &raw)?,
+            Err(_) => vec![],
+        },
+    };
+    let entries = fields.iter().map(|raw| decode(hash.as_str(), raw)).collect::<Result<Vec<ComponentEntry>, StoreError>>()?;
+    Ok((snapshot, entries))
+}
+
+/// Append a block to the journal, with components ids only, capped to JOURNAL_MAXLEN entries
+pub async fn journal(store: &Store, key: &str, event: &BlockEvent) -> Result<String, StoreError> {
+    let added = event.added.iter().map(|x| x.id.to_lowercase()).collect::<Vec<String>>();
+    let fields = vec![
+        ("block".to_string(), event.block.to_string()),
+        ("updated".to_string(), serde_json::to_string(&event.updated).unwrap_or_default()),
+        ("added".to_string(), serde_json::to_string(&added).unwrap_or_default()),
+        ("removed".to_string(), serde_json::to_string(&event.removed).unwrap_or_default()),
+    ];
+    store.xadd(key, JOURNAL_MAXLEN, fields).await
+}
+
+/// Read up to count journal entries strictly after from ("0" for the oldest entry still kept)
+/// Consumers resume after a disconnect by passing the id of the last entry they processed
+pub async fn read_journal(store: &Store, key: &str, from: &str, count: usize) -> Result<Vec<JournalEntry>, StoreError> {
+    let entries = store.xread(key, from, count).await?;
+    let mut result = vec![];
+    for (id, fields) in entries {
+        let list = |name: &str| fields.get(name).and_then(|x| serde_json::from_str::<Vec<String>>(x).ok());
+        let block = fields.get("block").and_then(|x| x.parse::<u64>().ok());
+        match (block, list("updated"), list("added"), list("removed")) {
+            (Some(block), Some(updated), Some(added), Some(removed)) => result.push(JournalEntry { id, block, updated, added, removed }),
+            _ => {
+                tracing::error!("📕 Malformed journal entry '{}' in '{}'", id, key);
             }
         }
-        Err(e) => {
-            tracing::error!("📕 Redis connection error: {}", e);
-            None
+    }
+    Ok(result)
+}
+
+/// Append a change of the token list to the feed, capped to TOKEN_FEED_MAXLEN entries
+pub async fn token_diff(store: &Store, key: &str, added: &[SrzToken], removed: &[String]) -> Result<String, StoreError> {
+    let fields = vec![
+        ("timestamp".to_string(), current_timestamp().to_string()),
+        ("added".to_string(), serde_json::to_string(added).unwrap_or_default()),
+        ("removed".to_string(), serde_json::to_string(removed).unwrap_or_default()),
+    ];
+    store.xadd(key, TOKEN_FEED_MAXLEN, fields).await
+}
+
+/// Read up to count token list changes strictly after from ("0" for the oldest entry still kept)
+pub async fn read_token_feed(store: &Store, key: &str, from: &str, count: usize) -> Result<Vec<TokenDiff>, StoreError> {
+    let entries = store.xread(key, from, count).await?;
+    let mut result = vec![];
+    for (id, fields) in entries {
+        let timestamp = fields.get("timestamp").and_then(|x| x.parse::<u64>().ok());
+        let added = fields.get("added").and_then(|x| serde_json::from_str::<Vec<SrzToken>>(x).ok());
+        let removed = fields.get("removed").and_then(|x| serde_json::from_str::<Vec<String>>(x).ok());
+        match (timestamp, added, removed) {
+            (Some(timestamp), Some(added), Some(removed)) => result.push(TokenDiff { id, timestamp, added, removed }),
+            _ => {
+                tracing::error!("📕 Malformed token feed entry '{}' in '{}'", id, key);
+            }
         }
     }
+    Ok(result)
 }
@@ -0,0 +1,110 @@
+use tycho_orderbook::builder::OrderbookBuilderConfig;
+use tycho_simulation::tycho_client::feed::component_tracker::ComponentFilter;
+
+use crate::types::StreamFilters;
+
+impl StreamFilters {
+    /// Build the filters of a network from STREAM_FILTER_<NETWORK>, or STREAM_FILTER
+    pub fn from_env(network: &str) -> Self {
+        let value = std::env::var(format!("STREAM_FILTER_{}", network.to_uppercase()))
+            .or(std::env::var("STREAM_FILTER"))
+            .unwrap_or_default();
+        StreamFilters::parse(network, value.as_str())
+    }
+
+    /// Parse a stream filter, e.g. "allow=uniswap_v2|uniswap_v3,deny=vm:curve,min_tvl=10,min_token_quality=100,max_days_since_last_trade=30"
+    /// Lists are separated by '|', missing or invalid fields are left unset (no filtering)
+    pub fn parse(network: &str, value: &str) -> Self {
+        let mut filters = StreamFilters::default();
+        let list = |x: &str| x.split('|').map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()).collect::<Vec<String>>();
+        for field in value.split(',') {
+            let Some((name, value)) = field.split_once('=') else {
+                continue;
+            };
+            match (name.trim(), value.trim()) {
+                ("allow", x) => filters.allow = list(x),
+                ("deny", x) => filters.deny = list(x),
+                ("min_tvl", x) => filters.min_tvl = x.parse::<f64>().ok().filter(|x| *x >= 0.),
+                ("min_token_quality", x) => filters.min_token_quality = x.parse().ok(),
+                ("max_days_since_last_trade", x) => filters.max_days_since_last_trade = x.parse().ok(),
+                (name, _) => tracing::warn!("Unknown field '{}' in stream filter of {}", name, network),
+            }
+        }
+        filters
+    }
+
+    /// True if the components of the protocol system are streamed
+    pub fn allows(&self, protocol: &str) -> bool {
+        let protocol = protocol.to_lowercase();
+        (self.allow.is_empty() || self.allow.contains(&protocol)) && !self.deny.contains(&protocol)
+    }
+
+    /// True if some protocol systems are filtered out
+    pub fn restricts(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    /// True if the token list must be fetched with custom quality thresholds
+    pub fn tokens(&self) -> bool {
+        self.min_token_quality.is_some() || self.max_days_since_last_trade.is_some()
+    }
+
+    /// Configuration of the OrderbookBuilder, None (SDK defaults) without a TVL filter
+    /// Components are added above min_tvl and removed below it. Protocol systems are filtered on each stream message, the builder subscribes to all of them
+    pub fn builder(&self) -> Option<OrderbookBuilderConfig> {
+        self.min_tvl.map(|tvl| OrderbookBuilderConfig {
+            filter: ComponentFilter::with_tvl_range(tvl, tvl),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::StreamFilters;
+
+    #[test]
+    fn parse() {
+        let filters = StreamFilters::parse(
+            "ethereum",
+            "allow=Uniswap_v2| uniswap_v3 ,deny=vm:curve,min_tvl=10.5,min_token_quality=100,max_days_since_last_trade=30",
+        );
+        assert_eq!(filters.allow, vec!["uniswap_v2".to_string(), "uniswap_v3".to_string()]);
+        assert_eq!(filters.deny, vec!["vm:curve".to_string()]);
+        assert_eq!(filters.min_tvl, Some(10.5));
+        assert_eq!(filters.min_token_quality, Some(100));
+        assert_eq!(filters.max_days_since_last_trade, Some(30));
+        assert!(filters.builder().is_some());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let filters = StreamFilters::parse("ethereum", "min_tvl=-1,min_token_quality=high,unknown=1,allow");
+        assert!(filters.allow.is_empty());
+        assert_eq!(filters.min_tvl, None);
+        assert_eq!(filters.min_token_quality, None);
+        assert!(!filters.restricts());
+        assert!(!filters.tokens());
+        assert!(filters.builder().is_none());
+        let filters = StreamFilters::parse("ethereum", "");
+        assert!(!filters.restricts());
+        assert!(filters.allows("uniswap_v2"));
+    }
+
+    #[test]
+    fn allows() {
+        let filters = StreamFilters::parse("ethereum", "allow=uniswap_v2|uniswap_v3");
+        assert!(filters.restricts());
+        assert!(filters.allows("uniswap_v2"));
+        assert!(filters.allows("UNISWAP_V3"));
+        assert!(!filters.allows("vm:curve"));
+        let filters = StreamFilters::parse("ethereum", "deny=vm:curve");
+        assert!(filters.restricts());
+        assert!(filters.allows("uniswap_v2"));
+        assert!(!filters.allows("vm:Curve"));
+        // Deny wins over allow
+        let filters = StreamFilters::parse("ethereum", "allow=uniswap_v2|vm:curve,deny=vm:curve");
+        assert!(filters.allows("uniswap_v2"));
+        assert!(!filters.allows("vm:curve"));
+        assert!(!filters.allows("sushiswap_v2"));
+    }
+}
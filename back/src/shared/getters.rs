@@ -5,42 +5,71 @@ use tycho_orderbook::{
 
 use crate::{
     data::keys,
-    types::{PairTag, Status},
+    store::{Store, StoreError},
+    types::{ComponentEntry, PairTag, RestartInfo, Snapshot, Status},
 };
 
 /// Get components for a given network
-pub async fn components(network: Network) -> Option<Vec<SrzProtocolComponent>> {
+pub async fn components(store: &Store, network: Network) -> Result<Vec<SrzProtocolComponent>, StoreError> {
     let key = keys::stream::components(network.name.clone());
-    crate::data::get::<Vec<SrzProtocolComponent>>(key.as_str()).await
+    let entries = crate::data::hvals::<ComponentEntry>(store, key.as_str()).await?;
+    Ok(entries.into_iter().map(|x| x.component).collect())
+}
+
+/// Get one component entry (component and block of its last update) for a given network
+pub async fn component(store: &Store, network: Network, id: String) -> Result<ComponentEntry, StoreError> {
+    let key = keys::stream::components(network.name.clone());
+    crate::data::hget::<ComponentEntry>(store, key.as_str(), id.to_lowercase().as_str()).await
 }
 
 /// Get tokens for a given network
-pub async fn tokens(network: Network) -> Option<Vec<SrzToken>> {
+pub async fn tokens(store: &Store, network: Network) -> Result<Vec<SrzToken>, StoreError> {
     let key = keys::stream::tokens(network.name.clone());
-    crate::data::get::<Vec<SrzToken>>(key.as_str()).await
+    crate::data::get::<Vec<SrzToken>>(store, key.as_str()).await
+}
+
+/// Get the latest block synced for a given network
+pub async fn latest(store: &Store, network: Network) -> Result<u64, StoreError> {
+    let key = keys::stream::latest(network.name.clone());
+    crate::data::get::<u64>(store, key.as_str()).await
+}
+
+/// Get the per-block keys (latest block, status, updated components) of a given network, tied to the same block
+pub async fn snapshot(store: &Store, network: Network) -> Result<Snapshot, StoreError> {
+    let (snapshot, _) = crate::data::snapshot(store, network.name.clone(), false).await?;
+    Ok(snapshot)
+}
+
+/// Get components for a given network, along with the snapshot they were read from
+pub async fn components_at(store: &Store, network: Network) -> Result<(Snapshot, Vec<SrzProtocolComponent>), StoreError> {
+    let (snapshot, entries) = crate::data::snapshot(store, network.name.clone(), true).await?;
+    Ok((snapshot, entries.into_iter().map(|x| x.component).collect()))
+}
+
+/// True while the data of a given network is restored from a checkpoint, before the stream is running again
+pub async fn stale(store: &Store, network: Network) -> bool {
+    snapshot(store, network).await.map(|x| x.stale()).unwrap_or(false)
 }
 
 /// Get status of the API
-pub async fn status(network: Network) -> Option<Status> {
-    let key1 = keys::stream::status(network.name.clone());
-    let key2 = keys::stream::latest(network.name.clone());
-    let stream = crate::data::get::<u128>(key1.as_str()).await;
-    let latest = crate::data::get::<u64>(key2.as_str()).await;
-    match (stream, latest) {
-        (Some(stream), Some(latest)) => Some(Status { stream, latest: latest.to_string() }),
-        _ => None,
-    }
+pub async fn status(store: &Store, network: Network) -> Result<Status, StoreError> {
+    let snapshot = snapshot(store, network.clone()).await?;
+    let key = keys::stream::restart(network.name.clone());
+    let restart = crate::data::get::<RestartInfo>(store, key.as_str()).await.ok();
+    Ok(Status {
+        stream: snapshot.stream,
+        latest: snapshot.block.to_string(),
+        snapshot: snapshot.version,
+        stale: snapshot.stale(),
+        restart,
+        store: store.status().await,
+    })
 }
 
 /// Get components for a given network
-pub async fn pairs(network: Network) -> Option<Vec<PairTag>> {
-    let key = keys::stream::components(network.name.clone());
-    match crate::data::get::<Vec<SrzProtocolComponent>>(key.as_str()).await {
-        Some(components) => {
-            let pairs = crate::helpers::generate_pair_tags(&components);
-            tracing::info!("Generate {} uniq pairs.", pairs.len());
-            Some(pairs)
-        }
-        None => None,
-    }
+pub async fn pairs(store: &Store, network: Network) -> Result<Vec<PairTag>, StoreError> {
+    let components = components(store, network).await?;
+    let pairs = crate::helpers::generate_pair_tags(&components);
+    tracing::info!("Generate {} uniq pairs.", pairs.len());
+    Ok(pairs)
 }
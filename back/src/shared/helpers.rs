@@ -3,25 +3,34 @@ use std::{collections::HashSet, process::Command, time::Duration};
 use axum::http::HeaderMap;
 use tycho_orderbook::{
     core::client::get_latest_block,
-    data::fmt::SrzProtocolComponent,
+    data::fmt::{SrzProtocolComponent, SrzToken},
     types::{Network, Orderbook},
+    utils::misc::current_timestamp,
 };
 
 use crate::{
     data::keys,
     getters,
-    misc::r#static::{HEADER_TYCHO_API_KEY, HEARTBEAT_DELAY},
-    types::{EnvAPIConfig, PairTag, StreamState},
+    misc::r#static::{HEADER_TYCHO_API_KEY, HEARTBEAT_DELAY, MAX_PAGE_LIMIT},
+    store::Store,
+    types::{ApiError, EnvAPIConfig, ListParams, Page, PairTag, StreamState},
 };
 
 /// Verify orderbook cache
 /// If the orderbook is not in the cache, the function will be computed
 /// If the orderbook is in the cache, check
-pub async fn verify_obcache(network: Network, acps: Vec<SrzProtocolComponent>, tag: String) -> Option<Orderbook> {
+/// Cached orderbooks are normally dropped by the invalidation listener, the timestamp check below is a fallback for missed messages
+/// Orderbooks computed more than max_age seconds ago are ignored
+pub async fn verify_obcache(store: &Store, network: Network, acps: Vec<SrzProtocolComponent>, tag: String, max_age: u64) -> Option<Orderbook> {
     let key = keys::stream::orderbook(network.name.clone(), tag);
-    match crate::data::get::<Orderbook>(key.as_str()).await {
-        Some(orderbook) => {
+    match crate::data::get::<Orderbook>(store, key.as_str()).await {
+        Ok(orderbook) => {
             tracing::info!("Orderbook found in cache, at block {} and timestamp: {}", orderbook.block, orderbook.timestamp);
+            let age = current_timestamp().saturating_sub(orderbook.timestamp);
+            if age > max_age {
+                tracing::debug!("Cached orderbook too old ({} seconds, max {})", age, max_age);
+                return None;
+            }
             let pools = orderbook.pools.clone();
             for previous in pools {
                 if let Some(current) = acps.iter().find(|x| x.id.to_lowercase() == previous.id.to_lowercase()) {
@@ -39,8 +48,8 @@ pub async fn verify_obcache(network: Network, acps: Vec<SrzProtocolComponent>, t
             tracing::debug!("Orderbook is up to date");
             return Some(orderbook);
         }
-        _ => {
-            tracing::info!("Couldn't find orderbook in cache");
+        Err(e) => {
+            tracing::info!("Couldn't use orderbook from cache: {}", e);
         }
     }
     None
@@ -68,25 +77,25 @@ pub fn validate_headers(headers: &HeaderMap, expected: String) -> (bool, String)
 
 /// Prevalidation of the API
 /// Check if the API stream is initialised and running, and if the API key is valid
-pub async fn prevalidation(network: Network, headers: HeaderMap, initialised: bool, key: String) -> Option<String> {
+pub async fn prevalidation(store: &Store, network: Network, headers: HeaderMap, initialised: bool, key: String) -> Option<ApiError> {
     // Check if the API stream is initialised
     if !initialised {
         let msg = "API is not yet initialised";
         tracing::warn!("{}", msg);
-        return Some(msg.to_string());
+        return Some(ApiError::NotInitialised(msg.to_string()));
     }
     // Check if the API is running
     // @dev Tmp => No error return, we keep answering requests with degraded stream synchronization, at worse data is a little outdated
-    match getters::status(network.clone()).await {
-        Some(status) => {
+    match getters::status(store, network.clone()).await {
+        Ok(status) => {
             if status.stream != StreamState::Running as u128 {
                 let msg = format!("API is not yet running: got {:?} vs {:?}", status.stream, StreamState::Running);
                 tracing::error!("{}", msg);
                 // return Some(msg.to_string());
             }
         }
-        _ => {
-            let msg = "Failed to get API status";
+        Err(e) => {
+            let msg = format!("Failed to get API status: {}", e);
             tracing::error!("{}", msg);
             // return Some(msg.to_string());
         }
@@ -95,11 +104,56 @@ pub async fn prevalidation(network: Network, headers: HeaderMap, initialised: bo
     let (allowed, msg) = validate_headers(&headers, key);
     if !allowed {
         tracing::error!("{}", msg);
-        return Some(msg.to_string());
+        return Some(ApiError::Unauthorized(msg));
     }
     None
 }
 
+/// Find a token by address or by symbol (case insensitive)
+/// A symbol shared by several tokens is rejected, listing the candidate addresses
+pub fn resolve(atks: &[SrzToken], query: &str) -> Result<SrzToken, ApiError> {
+    let query = query.trim().to_lowercase();
+    if query.starts_with("0x") {
+        return match atks.iter().find(|x| x.address.to_lowercase() == query) {
+            Some(token) => Ok(token.clone()),
+            None => {
+                let msg = format!("Couldn't find token with address {}", query);
+                tracing::error!("{}", msg);
+                Err(ApiError::UnknownToken(msg))
+            }
+        };
+    }
+    let candidates = atks.iter().filter(|x| x.symbol.to_lowercase() == query).collect::<Vec<&SrzToken>>();
+    match candidates.len() {
+        0 => {
+            let msg = format!("Couldn't find token with symbol {}", query.to_uppercase());
+            tracing::error!("{}", msg);
+            Err(ApiError::UnknownToken(msg))
+        }
+        1 => Ok(candidates[0].clone()),
+        _ => {
+            let addresses = candidates.iter().map(|x| x.address.to_lowercase()).collect::<Vec<String>>();
+            let msg = format!("Symbol {} is ambiguous, use one of the candidate addresses: {}", query.to_uppercase(), addresses.join(", "));
+            tracing::error!("{}", msg);
+            Err(ApiError::AmbiguousSymbol(msg))
+        }
+    }
+}
+
+/// Parse a pair tag and find both tokens in the Tycho token list
+/// Each side of the tag is either an address or a symbol: "0xt0-0xt1", "WETH-USDC", "WETH-0xt1"
+pub fn pair(atks: &[SrzToken], tag: &str) -> Result<(SrzToken, SrzToken), ApiError> {
+    let targets = tag.split("-").map(|x| x.to_string().to_lowercase()).collect::<Vec<String>>();
+    if targets.len() != 2 {
+        let msg = format!("Couldn't find the pair of tokens for tag {} - Query param Tag must contain only 2 tokens separated by a dash '-'", tag);
+        tracing::error!("{}", msg);
+        return Err(ApiError::BadRequest(msg));
+    }
+    let srzt0 = resolve(atks, targets[0].as_str())?;
+    let srzt1 = resolve(atks, targets[1].as_str())?;
+    Ok((srzt0, srzt1))
+}
+
 /// Generate all unique unordered pairs based on token address from a slice of protocol components.
 /// Each component's tokens are paired and uniqueness is enforced on the pair (addrbase, addrquote).
 pub fn generate_pair_tags(components: &[SrzProtocolComponent]) -> Vec<PairTag> {
@@ -137,6 +191,139 @@ pub fn generate_pair_tags(components: &[SrzProtocolComponent]) -> Vec<PairTag> {
     pairs
 }
 
+/// Split a sort param into its field and direction ('-' prefix = descending)
+fn sorting(params: &ListParams, fields: &[&str]) -> Result<Option<(String, bool)>, ApiError> {
+    match params.sort.as_ref() {
+        Some(sort) => {
+            let descending = sort.starts_with('-');
+            let field = sort.trim_start_matches('-').to_lowercase();
+            if !fields.contains(&field.as_str()) {
+                return Err(ApiError::BadRequest(format!("Invalid sort '{}', expected one of {:?}", sort, fields)));
+            }
+            Ok(Some((field, descending)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Filter and sort components according to list params
+pub fn filter_components(components: Vec<SrzProtocolComponent>, params: &ListParams) -> Result<Vec<SrzProtocolComponent>, ApiError> {
+    let sort = sorting(params, &["id", "protocol", "updated"])?;
+    let mut components = components
+        .into_iter()
+        .filter(|x| params.protocol_system.as_ref().is_none_or(|p| x.protocol_system.to_lowercase() == p.to_lowercase()))
+        .filter(|x| params.token.as_ref().is_none_or(|t| x.tokens.iter().any(|tk| tk.address.to_lowercase() == t.to_lowercase())))
+        .filter(|x| params.symbol.as_ref().is_none_or(|s| x.tokens.iter().any(|tk| tk.symbol.to_lowercase() == s.to_lowercase())))
+        .filter(|x| params.updated_since.is_none_or(|ts| x.last_updated_at >= ts))
+        .collect::<Vec<SrzProtocolComponent>>();
+    // Without sort, components are ordered by id so that pages are stable across requests
+    let (field, descending) = sort.unwrap_or(("id".to_string(), false));
+    components.sort_by(|a, b| {
+        let id = a.id.to_lowercase().cmp(&b.id.to_lowercase());
+        let ordering = match field.as_str() {
+            "protocol" => a.protocol_system.cmp(&b.protocol_system).then(id),
+            "updated" => a.last_updated_at.cmp(&b.last_updated_at).then(id),
+            _ => id,
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    Ok(components)
+}
+
+/// Filter and sort tokens according to list params
+pub fn filter_tokens(tokens: Vec<SrzToken>, params: &ListParams) -> Result<Vec<SrzToken>, ApiError> {
+    let sort = sorting(params, &["address", "symbol"])?;
+    let mut tokens = tokens
+        .into_iter()
+        .filter(|x| params.token.as_ref().is_none_or(|t| x.address.to_lowercase() == t.to_lowercase()))
+        .filter(|x| params.symbol.as_ref().is_none_or(|s| x.symbol.to_lowercase() == s.to_lowercase()))
+        .collect::<Vec<SrzToken>>();
+    // Without sort, tokens are ordered by address so that pages are stable across requests
+    let (field, descending) = sort.unwrap_or(("address".to_string(), false));
+    tokens.sort_by(|a, b| {
+        let address = a.address.to_lowercase().cmp(&b.address.to_lowercase());
+        let ordering = match field.as_str() {
+            "symbol" => a.symbol.to_lowercase().cmp(&b.symbol.to_lowercase()).then(address),
+            _ => address,
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    Ok(tokens)
+}
+
+/// Filter and sort pairs according to list params. Component filters must be applied before generating the pairs
+pub fn filter_pairs(pairs: Vec<PairTag>, params: &ListParams) -> Result<Vec<PairTag>, ApiError> {
+    let sort = sorting(params, &["base", "quote"])?;
+    let mut pairs = pairs
+        .into_iter()
+        .filter(|x| {
+            params
+                .token
+                .as_ref()
+                .is_none_or(|t| x.addrbase.to_lowercase() == t.to_lowercase() || x.addrquote.to_lowercase() == t.to_lowercase())
+        })
+        .filter(|x| {
+            params
+                .symbol
+                .as_ref()
+                .is_none_or(|s| x.base.to_lowercase() == s.to_lowercase() || x.quote.to_lowercase() == s.to_lowercase())
+        })
+        .collect::<Vec<PairTag>>();
+    // Without sort, pairs are ordered by base then quote address so that pages are stable across requests
+    let (field, descending) = sort.unwrap_or(("address".to_string(), false));
+    pairs.sort_by(|a, b| {
+        let address = (a.addrbase.to_lowercase(), a.addrquote.to_lowercase()).cmp(&(b.addrbase.to_lowercase(), b.addrquote.to_lowercase()));
+        let ordering = match field.as_str() {
+            "base" => a.base.to_lowercase().cmp(&b.base.to_lowercase()).then(address),
+            "quote" => a.quote.to_lowercase().cmp(&b.quote.to_lowercase()).then(address),
+            _ => address,
+        };
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    Ok(pairs)
+}
+
+/// Slice a filtered list into a page. The cursor is the offset of the first item of the page
+/// Without limit, every item from the cursor is returned
+pub fn paginate<T>(items: Vec<T>, params: &ListParams) -> Result<Page<T>, ApiError> {
+    let total = items.len();
+    let offset = match params.cursor.as_ref() {
+        Some(cursor) => cursor.parse::<usize>().map_err(|_| ApiError::BadRequest(format!("Invalid cursor '{}'", cursor)))?,
+        None => 0,
+    };
+    if let Some(limit) = params.limit {
+        if limit == 0 || limit > MAX_PAGE_LIMIT {
+            return Err(ApiError::BadRequest(format!("Limit must be between 1 and {}, got {}", MAX_PAGE_LIMIT, limit)));
+        }
+    }
+    let end = match params.limit {
+        Some(limit) => (offset + limit).min(total),
+        None => total,
+    };
+    let next = if end < total { Some(end.to_string()) } else { None };
+    let items = items.into_iter().skip(offset).take(end.saturating_sub(offset)).collect::<Vec<T>>();
+    Ok(Page {
+        items,
+        total,
+        limit: params.limit,
+        cursor: params.cursor.clone(),
+        next,
+        stale: false,
+    })
+}
+
 /// Get the current Git commit hash
 pub fn commit() -> Option<String> {
     let output = Command::new("git").args(["rev-parse", "HEAD"]).output();
@@ -176,7 +363,7 @@ pub async fn alive(endpoint: String) -> bool {
 /// Conditional heartbeat, with a dedicated task. Not used for now.
 /// 1. Fetch Redis data size > 0
 /// 2. Assert Network status latest > 0
-pub async fn hearbeats(networks: Vec<Network>, config: EnvAPIConfig) {
+pub async fn hearbeats(store: Store, networks: Vec<Network>, config: EnvAPIConfig) {
     if config.testing {
         tracing::info!("Testing mode, heartbeat task not spawned.");
         return;
@@ -189,8 +376,8 @@ pub async fn hearbeats(networks: Vec<Network>, config: EnvAPIConfig) {
             hb.tick().await;
             tracing::debug!("Heartbeat tick");
             for (x, network) in networks.clone().iter().enumerate() {
-                match crate::getters::status(network.clone()).await {
-                    Some(data) => {
+                match crate::getters::status(&store, network.clone()).await {
+                    Ok(data) => {
                         let latest_remote = get_latest_block(network.rpc.clone()).await;
                         let latest_local = data.latest.parse::<u64>().unwrap_or_default();
                         let delta = latest_remote - latest_local;
@@ -221,8 +408,8 @@ pub async fn hearbeats(networks: Vec<Network>, config: EnvAPIConfig) {
                             );
                         }
                     }
-                    None => {
-                        tracing::error!("Heartbeat Error: No data for network {}", network.name);
+                    Err(e) => {
+                        tracing::error!("Heartbeat Error: No data for network {}: {}", network.name, e);
                     }
                 }
             }
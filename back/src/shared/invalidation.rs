@@ -0,0 +1,92 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use tokio::sync::{broadcast::error::RecvError, RwLock};
+use tycho_orderbook::types::Network;
+
+use crate::{data::keys, store::Store, types::Invalidation};
+
+/// Orderbook cache keys written by this replica, by component id
+/// Used to drop the cached orderbooks depending on a component as soon as it is updated, whichever replica streamed the update
+pub type Index = Arc<RwLock<HashMap<String, HashSet<String>>>>;
+
+/// Keep track of an orderbook cache key, for each component it was computed with
+pub async fn track(index: &Index, key: String, pools: Vec<String>) {
+    let mut mtx = index.write().await;
+    for pool in pools {
+        mtx.entry(pool.to_lowercase()).or_default().insert(key.clone());
+    }
+}
+
+/// Forget the dropped cache keys under the other components they were computed with, and the components left without key
+/// Removed pairs are listed in the invalidation messages, so their entries are dropped along with their orderbooks
+fn prune(index: &mut HashMap<String, HashSet<String>>, keys: &HashSet<String>) {
+    if keys.is_empty() {
+        return;
+    }
+    index.retain(|_, tracked| {
+        tracked.retain(|x| !keys.contains(x));
+        !tracked.is_empty()
+    });
+}
+
+/// Publish the components updated (or removed) at a block, to every replica
+pub async fn publish(store: &Store, network: Network, message: &Invalidation) {
+    if message.components.is_empty() {
+        return;
+    }
+    let channel = keys::stream::invalidations(network.name.clone());
+    match serde_json::to_string(message) {
+        Ok(payload) => {
+            // Failure logged by the store, replicas fall back to cache timestamps
+            let _ = store.publish(channel.as_str(), payload).await;
+        }
+        Err(e) => tracing::error!("Failed to serialize invalidation message: {}", e),
+    }
+}
+
+/// Listen to invalidation messages and drop the cached orderbooks depending on the components listed
+/// If messages were missed (lagging), every orderbook tracked is dropped
+pub async fn listen(store: Store, network: Network, index: Index) {
+    let channel = keys::stream::invalidations(network.name.clone());
+    let mut rx = match store.subscribe(channel.as_str()).await {
+        Ok(rx) => rx,
+        Err(e) => {
+            tracing::error!("Failed to subscribe to invalidation channel {}: {}, falling back to cache timestamps only", channel, e);
+            return;
+        }
+    };
+    loop {
+        let keys = match rx.recv().await {
+            Ok(payload) => match serde_json::from_str::<Invalidation>(payload.as_str()) {
+                Ok(message) => {
+                    let mut mtx = index.write().await;
+                    let keys = message.components.iter().filter_map(|x| mtx.remove(&x.to_lowercase())).flatten().collect::<HashSet<String>>();
+                    prune(&mut mtx, &keys);
+                    keys
+                }
+                Err(e) => {
+                    tracing::error!("Invalid invalidation message on {}: {}", channel, e);
+                    continue;
+                }
+            },
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("Invalidation listener on {} lagging, skipped {} messages. Dropping every tracked orderbook", network.name, skipped);
+                let mut mtx = index.write().await;
+                mtx.drain().flat_map(|(_, keys)| keys).collect::<HashSet<String>>()
+            }
+            Err(RecvError::Closed) => {
+                tracing::error!("Invalidation channel {} closed, falling back to cache timestamps only", channel);
+                return;
+            }
+        };
+        if !keys.is_empty() {
+            tracing::debug!("Dropping {} cached orderbooks on {}", keys.len(), network.name);
+        }
+        for key in keys {
+            let _ = store.delete(key.as_str()).await;
+        }
+    }
+}
@@ -1,5 +1,14 @@
+pub mod codec;
 pub mod data;
+pub mod filters;
 pub mod getters;
 pub mod helpers;
+pub mod invalidation;
 pub mod misc;
+pub mod quote;
+pub mod restart;
+pub mod route;
+pub mod store;
+pub mod supervisor;
+pub mod sync;
 pub mod types;
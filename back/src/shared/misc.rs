@@ -11,8 +11,26 @@ pub mod r#static {
     pub static HEADER_TYCHO_API_KEY: &str = "tycho-orderbook-web-api-key";
     pub static TMP_HD_VALUE: &str = "42";
     pub static HEARTBEAT_DELAY: u64 = 300; // 900
-    pub static CACHE_OB_DURATION: i64 = 300; // If computed less than 300 seconds ago, use the cached orderbook .. even if state has changed (slightly or entirely)
-    pub static RESTART_STREAM_DELAY: u64 = 150; // If computed less than 60 seconds ago, use the cached orderbook .. even if state has changed (slightly or entirely)
+    pub static CACHE_OB_DURATION: u64 = 300; // Default TTL of cached orderbooks (CACHE_OB_DURATION), if computed less than 300 seconds ago the cached orderbook can be used .. even if state has changed (slightly or entirely)
+    pub static RESTART_STREAM_DELAY: u64 = 150; // Default max delay (seconds) before restarting a stream task, reached after consecutive failures (RESTART_POLICY max)
+    pub static RESTART_MIN_DELAY: u64 = 5; // Default delay (seconds) before the first restart after a failure (RESTART_POLICY min)
+    pub static RESTART_FACTOR: f64 = 2.; // Default backoff multiplier at each consecutive failure (RESTART_POLICY factor)
+    pub static METRICS_WINDOW: u64 = 60; // Window (seconds) of the messages per second rate reported on GET /{network}/stream
+    pub static RESTART_JITTER: f64 = 0.2; // Default random share of the restart delay, added or removed (RESTART_POLICY jitter)
+    pub static MAX_PAGE_LIMIT: usize = 5000; // Max number of items per page on list endpoints (/components, /tokens, /pairs)
+    pub static MAX_BATCH_ORDERBOOKS: usize = 25; // Max number of orderbooks requested at once on POST /orderbooks
+    pub static REDIS_CONNECTION_TIMEOUT_MS: u64 = 2000; // Default timeout of each Redis connection attempt (REDIS_CONNECTION_TIMEOUT_MS)
+    pub static REDIS_RESPONSE_TIMEOUT_MS: u64 = 2000; // Default timeout of each Redis command (REDIS_RESPONSE_TIMEOUT_MS)
+    pub static REDIS_RETRIES: usize = 6; // Default number of reconnection attempts, with exponential backoff (REDIS_RETRIES)
+    pub static REDIS_MAX_DELAY_MS: u64 = 5000; // Default max delay between two reconnection attempts (REDIS_MAX_DELAY_MS)
+    pub static BLOCK_EVENTS_CAPACITY: usize = 64; // Number of BlockEvent kept for slow SSE subscribers before they start lagging
+    pub static JOURNAL_MAXLEN: usize = 10000; // Approximate number of blocks kept in the journal stream (stream:journal:<network>)
+    pub static SUBSCRIPTION_CAPACITY: usize = 256; // Number of pub/sub messages kept for a slow subscriber before it starts lagging
+    pub static KEY_PREFIX: &str = "tycho-orderbook"; // Default prefix of every store key (KEY_PREFIX)
+    pub static TOKENS_REFRESH_INTERVAL: u64 = 3600; // Default delay (seconds) between two refreshes of the Tycho tokens (TOKENS_REFRESH_INTERVAL), 0 to disable
+    pub static TOKEN_FEED_MAXLEN: usize = 1000; // Approximate number of token list changes kept in the feed (stream:tokens:feed:<network>)
+    pub static CHECKPOINT_INTERVAL: u64 = 300; // Default delay (seconds) between two checkpoints of the streamed data (CHECKPOINT_INTERVAL), 0 to disable
+    pub static SCHEMA_VERSION: u32 = 2; // Version of the store keys layout, bumped (with a migration in data::migrate) when it changes
 }
 
 /// Read a file and return a Vec<T> where T is a deserializable type
@@ -72,6 +90,13 @@ impl EnvAPIConfig {
             tycho_api_key: get("TYCHO_API_KEY"),
             web_api_key: get("WEB_API_KEY"),
             api_port: get("API_PORT"),
+            cache_ttl: std::env::var("CACHE_OB_DURATION").ok().and_then(|x| x.parse::<u64>().ok()).unwrap_or(r#static::CACHE_OB_DURATION),
+            drop_legacy_keys: std::env::var("DROP_LEGACY_KEYS").map(|x| x == "true").unwrap_or(false),
+            checkpoint_interval: std::env::var("CHECKPOINT_INTERVAL").ok().and_then(|x| x.parse::<u64>().ok()).unwrap_or(r#static::CHECKPOINT_INTERVAL),
+            tokens_refresh: std::env::var("TOKENS_REFRESH_INTERVAL")
+                .ok()
+                .and_then(|x| x.parse::<u64>().ok())
+                .unwrap_or(r#static::TOKENS_REFRESH_INTERVAL),
         }
     }
 }
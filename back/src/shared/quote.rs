@@ -0,0 +1,85 @@
+use num_bigint::BigUint;
+use tycho_orderbook::types::ProtoSimComp;
+use tycho_simulation::models::Token;
+
+use crate::types::{Quote, QuoteSplit};
+
+/// Number of chunks the amount is split into, each chunk being routed to the pool with the best output
+pub static QUOTE_CHUNKS: u32 = 10;
+
+/// Convert a human readable amount to its raw value
+fn raw(amount: f64, decimals: usize) -> BigUint {
+    BigUint::from((amount * 10f64.powi(decimals as i32)).floor() as u128)
+}
+
+/// Convert a raw amount to its human readable value
+fn human(amount: BigUint, decimals: usize) -> f64 {
+    amount.to_string().parse::<f64>().unwrap_or_default() / 10f64.powi(decimals as i32)
+}
+
+/// Quote a single amount of sell token for buy token, across the given pools
+/// The amount is split in QUOTE_CHUNKS chunks, greedily sent to the pool giving the best output given its state after the previous chunks
+/// Mid price is the best spot price among pools, impact is expressed in percent versus mid
+pub fn quote(ptss: &[ProtoSimComp], sell: &Token, buy: &Token, amount: f64, block: u64) -> Option<Quote> {
+    if ptss.is_empty() || amount <= 0. {
+        return None;
+    }
+    let mid = ptss.iter().filter_map(|x| x.protosim.spot_price(sell, buy).ok()).fold(0., f64::max);
+    let mut states = ptss.iter().map(|x| x.protosim.clone()).collect::<Vec<_>>();
+    let mut split = ptss
+        .iter()
+        .map(|x| QuoteSplit {
+            component: x.component.id.to_lowercase(),
+            protocol: x.component.protocol_system.clone(),
+            amount_in: 0.,
+            amount_out: 0.,
+        })
+        .collect::<Vec<QuoteSplit>>();
+    let chunk = amount / QUOTE_CHUNKS as f64;
+    for _ in 0..QUOTE_CHUNKS {
+        let mut best = None;
+        for (x, state) in states.iter().enumerate() {
+            match state.get_amount_out(raw(chunk, sell.decimals), sell, buy) {
+                Ok(result) => {
+                    let output = human(result.amount.clone(), buy.decimals);
+                    let better = match &best {
+                        Some((_, current, _)) => output > *current,
+                        None => output > 0.,
+                    };
+                    if better {
+                        best = Some((x, output, result.new_state));
+                    }
+                }
+                Err(e) => {
+                    tracing::trace!("Quote: simulation failed on {}: {:?}", split[x].component, e);
+                }
+            }
+        }
+        match best {
+            Some((x, output, state)) => {
+                states[x] = state;
+                split[x].amount_in += chunk;
+                split[x].amount_out += output;
+            }
+            None => {
+                tracing::debug!("Quote: no pool can absorb another chunk of {} {}", chunk, sell.symbol);
+                return None;
+            }
+        }
+    }
+    let amount_out = split.iter().map(|x| x.amount_out).sum::<f64>();
+    let price = amount_out / amount;
+    let impact = if mid > 0. { (mid - price) / mid * 100. } else { 0. };
+    split.retain(|x| x.amount_in > 0.);
+    Some(Quote {
+        sell: sell.address.to_string().to_lowercase(),
+        buy: buy.address.to_string().to_lowercase(),
+        amount_in: amount,
+        amount_out,
+        price,
+        mid,
+        impact,
+        split,
+        block,
+    })
+}
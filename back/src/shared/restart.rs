@@ -0,0 +1,65 @@
+use rand::Rng;
+
+use crate::misc::r#static::{RESTART_FACTOR, RESTART_JITTER, RESTART_MIN_DELAY, RESTART_STREAM_DELAY};
+
+/// Restart policy of a stream task: exponential backoff with jitter, capped to a max delay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPolicy {
+    // Delay (seconds) before the first retry
+    pub min: u64,
+    // Max delay (seconds), a session lasting longer is considered stable and resets the backoff
+    pub max: u64,
+    // Delay multiplier at each consecutive failure
+    pub factor: f64,
+    // Random share (0 to 1) of the delay added or removed, so that replicas don't reconnect at once
+    pub jitter: f64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            min: RESTART_MIN_DELAY,
+            max: RESTART_STREAM_DELAY,
+            factor: RESTART_FACTOR,
+            jitter: RESTART_JITTER,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Build the policy of a network from RESTART_POLICY_<NETWORK>, or RESTART_POLICY, e.g. "min=5,max=150,factor=2,jitter=0.2"
+    /// Missing or invalid fields keep their default value. Delays are divided by 10 in testing mode, down to 1 second
+    pub fn from_env(network: &str, testing: bool) -> Self {
+        let mut policy = RestartPolicy::default();
+        let value = std::env::var(format!("RESTART_POLICY_{}", network.to_uppercase()))
+            .or(std::env::var("RESTART_POLICY"))
+            .unwrap_or_default();
+        for field in value.split(',') {
+            let Some((name, value)) = field.split_once('=') else {
+                continue;
+            };
+            match (name.trim(), value.trim()) {
+                ("min", x) => policy.min = x.parse().unwrap_or(policy.min),
+                ("max", x) => policy.max = x.parse().unwrap_or(policy.max),
+                ("factor", x) => policy.factor = x.parse::<f64>().map(|x| x.max(1.)).unwrap_or(policy.factor),
+                ("jitter", x) => policy.jitter = x.parse::<f64>().map(|x| x.clamp(0., 1.)).unwrap_or(policy.jitter),
+                (name, _) => tracing::warn!("Unknown field '{}' in restart policy of {}", name, network),
+            }
+        }
+        policy.max = policy.max.max(policy.min);
+        if testing {
+            // At least 1 second, a zero delay would restart a failing stream in a tight loop
+            policy.min = (policy.min / 10).max(1);
+            policy.max = (policy.max / 10).max(policy.min);
+        }
+        policy
+    }
+
+    /// Delay (seconds) before the given retry (1 for the first one after a failure), jitter included
+    pub fn delay(&self, attempt: u32) -> u64 {
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let delay = (self.min as f64 * self.factor.powi(exponent)).min(self.max as f64);
+        let jitter = delay * self.jitter * rand::thread_rng().gen_range(-1.0..=1.0);
+        (delay + jitter).clamp(0., self.max as f64).round() as u64
+    }
+}
@@ -0,0 +1,99 @@
+use num_bigint::BigUint;
+use tycho_orderbook::{data::fmt::SrzToken, types::ProtoSimComp};
+use tycho_simulation::models::Token;
+
+use crate::types::{HopLevel, RouteHop};
+
+/// ETH-worth of each level of a synthetic multi-hop orderbook (1 = amount worth 1 ETH)
+pub static LEVELS: [f64; 10] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 25.0];
+
+/// Build the hops of a route from the component and token paths returned by maths::path::routing
+/// Return None if a component of the path has no protosim in the given list
+pub fn hops(ptss: &[ProtoSimComp], comp_path: &[String], token_path: &[String]) -> Option<Vec<RouteHop>> {
+    if comp_path.is_empty() || token_path.len() != comp_path.len() + 1 {
+        tracing::error!("Invalid route: {} components for {} tokens", comp_path.len(), token_path.len());
+        return None;
+    }
+    let mut hops = vec![];
+    for (x, id) in comp_path.iter().enumerate() {
+        let pts = ptss.iter().find(|p| p.component.id.to_lowercase() == id.to_lowercase())?;
+        hops.push(RouteHop {
+            component: id.to_lowercase(),
+            protocol: pts.component.protocol_system.clone(),
+            input: token_path[x].to_lowercase(),
+            output: token_path[x + 1].to_lowercase(),
+        });
+    }
+    Some(hops)
+}
+
+/// Find the tokens of a hop, converted for tycho-simulation
+fn tokens(atks: &[SrzToken], hop: &RouteHop) -> Option<(SrzToken, SrzToken)> {
+    let tin = atks.iter().find(|x| x.address.to_lowercase() == hop.input)?;
+    let tout = atks.iter().find(|x| x.address.to_lowercase() == hop.output)?;
+    Some((tin.clone(), tout.clone()))
+}
+
+/// Simulate an amount (human readable units) through each hop of the route, returning the output amount
+pub fn simulate(ptss: &[ProtoSimComp], atks: &[SrzToken], route: &[RouteHop], amount: f64) -> Option<f64> {
+    let mut amount = amount;
+    for hop in route {
+        let pts = ptss.iter().find(|x| x.component.id.to_lowercase() == hop.component)?;
+        let (tin, tout) = tokens(atks, hop)?;
+        let raw = BigUint::from((amount * 10f64.powi(tin.decimals as i32)).floor() as u128);
+        match pts.protosim.get_amount_out(raw, &Token::from(tin.clone()), &Token::from(tout.clone())) {
+            Ok(result) => {
+                amount = result.amount.to_string().parse::<f64>().unwrap_or_default() / 10f64.powi(tout.decimals as i32);
+            }
+            Err(e) => {
+                tracing::debug!("Simulation failed on hop {} ({} => {}): {:?}", hop.component, tin.symbol, tout.symbol, e);
+                return None;
+            }
+        }
+    }
+    Some(amount)
+}
+
+/// Spot price along the route (output per input), product of each hop spot price
+pub fn spot(ptss: &[ProtoSimComp], atks: &[SrzToken], route: &[RouteHop]) -> Option<f64> {
+    let mut price = 1.;
+    for hop in route {
+        let pts = ptss.iter().find(|x| x.component.id.to_lowercase() == hop.component)?;
+        let (tin, tout) = tokens(atks, hop)?;
+        match pts.protosim.spot_price(&Token::from(tin), &Token::from(tout)) {
+            Ok(p) => price *= p,
+            Err(e) => {
+                tracing::debug!("Spot price failed on hop {}: {:?}", hop.component, e);
+                return None;
+            }
+        }
+    }
+    Some(price)
+}
+
+/// Simulate each level of one side of a synthetic orderbook
+/// - size is the amount of input token worth 1 ETH
+/// - bid is true when selling base for quote, so that the price is always expressed in quote per base
+pub fn levels(ptss: &[ProtoSimComp], atks: &[SrzToken], route: &[RouteHop], size: f64, bid: bool) -> Vec<HopLevel> {
+    let ids = route.iter().map(|x| x.component.clone()).collect::<Vec<String>>();
+    let mut levels = vec![];
+    for multiplier in LEVELS.iter() {
+        let amount = multiplier * size;
+        match simulate(ptss, atks, route, amount) {
+            Some(output) if output > 0. => {
+                let price = if bid { output / amount } else { amount / output };
+                levels.push(HopLevel {
+                    amount,
+                    output,
+                    price,
+                    hops: ids.clone(),
+                });
+            }
+            _ => {
+                tracing::debug!("Level {} (amount {}) couldn't be simulated, stopping", multiplier, amount);
+                break;
+            }
+        }
+    }
+    levels
+}
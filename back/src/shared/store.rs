@@ -0,0 +1,616 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{self, Display},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use redis::{
+    aio::{ConnectionManager, ConnectionManagerConfig},
+    streams::StreamReadReply,
+    Client, FromRedisValue, RedisError,
+};
+use tokio::sync::{broadcast, OnceCell, RwLock};
+use tycho_orderbook::utils::misc::current_timestamp;
+
+use crate::{
+    misc::r#static::{REDIS_CONNECTION_TIMEOUT_MS, REDIS_MAX_DELAY_MS, REDIS_RESPONSE_TIMEOUT_MS, REDIS_RETRIES, SUBSCRIPTION_CAPACITY},
+    types::StoreHealth,
+};
+
+/// Error of the storage layer, telling apart a value not written yet from an unreachable backend or an unreadable value
+#[derive(Debug, Clone)]
+pub enum StoreError {
+    // Key (or hash field) not written
+    NotFound(String),
+    // Backend unreachable, or command failed
+    Connection(String),
+    // Value couldn't be encoded with the codec
+    Encode(String),
+    // Value couldn't be decoded with the codec
+    Decode(String),
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::NotFound(key) => write!(f, "{} not found", key),
+            StoreError::Connection(msg) => write!(f, "store unavailable: {}", msg),
+            StoreError::Encode(msg) => write!(f, "couldn't encode value: {}", msg),
+            StoreError::Decode(msg) => write!(f, "couldn't decode value: {}", msg),
+        }
+    }
+}
+
+impl From<RedisError> for StoreError {
+    fn from(e: RedisError) -> Self {
+        StoreError::Connection(e.to_string())
+    }
+}
+
+/// One write of a transaction, applied with the others of the same transaction or not at all
+#[derive(Debug, Clone)]
+pub enum WriteOp {
+    Set(String, Vec<u8>),
+    Delete(String),
+    HSet(String, Vec<(String, Vec<u8>)>),
+    HDel(String, Vec<String>),
+    // Increment an integer key (0 if missing)
+    Incr(String),
+}
+
+/// Storage backend shared by the stream and the API, holding raw (encoded) values
+/// Typed access is done by the functions of the data module, encoding values with the configured codec
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    /// Get a raw value, None if missing
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StoreError>;
+    /// Set a raw value
+    async fn set(&self, key: &str, value: Vec<u8>) -> Result<(), StoreError>;
+    /// Set a raw value expiring after ttl seconds
+    async fn set_ex(&self, key: &str, value: Vec<u8>, ttl: u64) -> Result<(), StoreError>;
+    /// Set several raw values at once
+    async fn mset(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), StoreError>;
+    /// Delete a key
+    async fn delete(&self, key: &str) -> Result<(), StoreError>;
+    /// List the keys starting with a prefix
+    async fn scan(&self, prefix: &str) -> Result<Vec<String>, StoreError>;
+    /// Set several fields of a hash at once
+    async fn hset(&self, key: &str, entries: Vec<(String, Vec<u8>)>) -> Result<(), StoreError>;
+    /// Get several fields of a hash, None for each missing field
+    async fn hmget(&self, key: &str, fields: Vec<String>) -> Result<Vec<Option<Vec<u8>>>, StoreError>;
+    /// Delete several fields of a hash
+    async fn hdel(&self, key: &str, fields: Vec<String>) -> Result<(), StoreError>;
+    /// Get all the values of a hash, empty if the hash doesn't exist (or is empty)
+    async fn hvals(&self, key: &str) -> Result<Vec<Vec<u8>>, StoreError>;
+    /// Apply several writes atomically (MULTI/EXEC for Redis), readers see all of them or none
+    async fn transaction(&self, ops: Vec<WriteOp>) -> Result<(), StoreError>;
+    /// Read several keys, and optionally all the values of a hash, atomically
+    async fn snapshot(&self, keys: Vec<String>, hash: Option<&str>) -> Result<(Vec<Option<Vec<u8>>>, Vec<Vec<u8>>), StoreError>;
+    /// Append an entry to a capped stream (approximate max length), returning its id
+    async fn xadd(&self, key: &str, maxlen: usize, fields: Vec<(String, String)>) -> Result<String, StoreError>;
+    /// Read up to count entries of a stream, strictly after the given id ("0" to read from the start)
+    async fn xread(&self, key: &str, from: &str, count: usize) -> Result<Vec<(String, HashMap<String, String>)>, StoreError>;
+    /// Publish a message on a channel, to every subscriber (all replicas for Redis)
+    async fn publish(&self, channel: &str, message: String) -> Result<(), StoreError>;
+    /// Subscribe to a channel. For Redis, the subscription is kept alive (resubscribing after a disconnection) by a background task
+    async fn subscribe(&self, channel: &str) -> Result<broadcast::Receiver<String>, StoreError>;
+    /// Health of the backend
+    async fn status(&self) -> StoreHealth;
+}
+
+/// Store shared across tasks, passed to handlers with an Extension
+pub type Store = Arc<dyn StateStore>;
+
+/// Build the store selected by the STORE env variable: 'redis' (default) or 'memory'
+pub fn from_env() -> Store {
+    match std::env::var("STORE").unwrap_or_default().to_lowercase().as_str() {
+        "memory" => {
+            tracing::info!("📕 Using in-memory store, data is not shared between processes");
+            Arc::new(InMemoryStore::default())
+        }
+        _ => {
+            let endpoint = std::env::var("REDIS_HOST");
+            let endpoint = match endpoint {
+                Ok(endpoint) => endpoint,
+                Err(_) => "127.0.0.1:42777".to_string(),
+            };
+            Arc::new(RedisStore::new(format!("redis://{}", endpoint)))
+        }
+    }
+}
+
+/// Read a numeric env variable, falling back to a default value
+fn env_or(key: &str, default: u64) -> u64 {
+    std::env::var(key).ok().and_then(|x| x.parse::<u64>().ok()).unwrap_or(default)
+}
+
+/// Redis backend, with one shared connection manager lazily initialised on first use
+/// If the initial connection fails, the next call retries it
+pub struct RedisStore {
+    endpoint: String,
+    manager: OnceCell<ConnectionManager>,
+    failures: AtomicU64,
+    last_error: Mutex<Option<(u64, String)>>,
+}
+
+impl RedisStore {
+    pub fn new(endpoint: String) -> Self {
+        RedisStore {
+            endpoint,
+            manager: OnceCell::new(),
+            failures: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// Keep track of a Redis error, for the health report
+    fn failure(&self, e: &RedisError) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = Some((current_timestamp(), e.to_string()));
+        }
+    }
+
+    /// Run a command on the shared connection, keeping track of the failures
+    /// action and key only give context to the error
+    async fn query<T: FromRedisValue>(&self, cmd: &redis::Cmd, action: &str, key: &str) -> Result<T, StoreError> {
+        let mut co = self.connect().await?;
+        match cmd.query_async::<T>(&mut co).await {
+            Ok(value) => Ok(value),
+            Err(err) => {
+                tracing::error!("📕 Failed to {} '{}': {}", action, key, err);
+                self.failure(&err);
+                Err(StoreError::from(err))
+            }
+        }
+    }
+
+    /// Return a handle on the shared connection manager, which reconnects automatically with exponential backoff
+    /// Timeouts and backoff are configurable with REDIS_CONNECTION_TIMEOUT_MS, REDIS_RESPONSE_TIMEOUT_MS, REDIS_RETRIES and REDIS_MAX_DELAY_MS
+    pub async fn connect(&self) -> Result<ConnectionManager, RedisError> {
+        let manager = self
+            .manager
+            .get_or_try_init(|| async {
+                let client = Client::open(self.endpoint.clone())?;
+                let config = ConnectionManagerConfig::new()
+                    .set_connection_timeout(Duration::from_millis(env_or("REDIS_CONNECTION_TIMEOUT_MS", REDIS_CONNECTION_TIMEOUT_MS)))
+                    .set_response_timeout(Duration::from_millis(env_or("REDIS_RESPONSE_TIMEOUT_MS", REDIS_RESPONSE_TIMEOUT_MS)))
+                    .set_number_of_retries(env_or("REDIS_RETRIES", REDIS_RETRIES as u64) as usize)
+                    .set_max_delay(env_or("REDIS_MAX_DELAY_MS", REDIS_MAX_DELAY_MS));
+                let manager = ConnectionManager::new_with_config(client, config).await?;
+                tracing::debug!("📕 Redis connection manager initialised");
+                Ok::<ConnectionManager, RedisError>(manager)
+            })
+            .await;
+        match manager {
+            Ok(manager) => Ok(manager.clone()),
+            Err(e) => {
+                tracing::error!("Redis Client Error: {}", e);
+                self.failure(&e);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl StateStore for RedisStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        self.query(redis::cmd("GET").arg(key), "get", key).await
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>) -> Result<(), StoreError> {
+        self.query(redis::cmd("SET").arg(key).arg(value), "set", key).await
+    }
+
+    async fn set_ex(&self, key: &str, value: Vec<u8>, ttl: u64) -> Result<(), StoreError> {
+        self.query(redis::cmd("SET").arg(key).arg(value).arg("EX").arg(ttl.max(1)), "set (with ttl)", key).await
+    }
+
+    async fn mset(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), StoreError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let context = format!("{} keys", entries.len());
+        self.query(redis::cmd("MSET").arg(&entries), "set", context.as_str()).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        self.query(redis::cmd("DEL").arg(key), "delete", key).await
+    }
+
+    async fn scan(&self, prefix: &str) -> Result<Vec<String>, StoreError> {
+        // Escape glob characters of the prefix, then iterate with SCAN to avoid blocking Redis like KEYS would
+        let pattern = format!("{}*", prefix.chars().map(|x| if "*?[]\\".contains(x) { format!("\\{}", x) } else { x.to_string() }).collect::<String>());
+        let mut cursor = 0u64;
+        let mut keys = vec![];
+        loop {
+            let (next, batch): (u64, Vec<String>) = self
+                .query(redis::cmd("SCAN").arg(cursor).arg("MATCH").arg(pattern.as_str()).arg("COUNT").arg(1000), "scan", pattern.as_str())
+                .await?;
+            keys.extend(batch);
+            if next == 0 {
+                return Ok(keys);
+            }
+            cursor = next;
+        }
+    }
+
+    async fn hset(&self, key: &str, entries: Vec<(String, Vec<u8>)>) -> Result<(), StoreError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.query(redis::cmd("HSET").arg(key).arg(&entries), "set fields of", key).await
+    }
+
+    async fn hmget(&self, key: &str, fields: Vec<String>) -> Result<Vec<Option<Vec<u8>>>, StoreError> {
+        if fields.is_empty() {
+            return Ok(vec![]);
+        }
+        self.query(redis::cmd("HMGET").arg(key).arg(&fields), "get fields of", key).await
+    }
+
+    async fn hdel(&self, key: &str, fields: Vec<String>) -> Result<(), StoreError> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+        self.query(redis::cmd("HDEL").arg(key).arg(&fields), "delete fields of", key).await
+    }
+
+    async fn hvals(&self, key: &str) -> Result<Vec<Vec<u8>>, StoreError> {
+        self.query(redis::cmd("HVALS").arg(key), "get values of", key).await
+    }
+
+    async fn transaction(&self, ops: Vec<WriteOp>) -> Result<(), StoreError> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for op in ops.iter() {
+            match op {
+                WriteOp::Set(key, value) => pipe.cmd("SET").arg(key).arg(value).ignore(),
+                WriteOp::Delete(key) => pipe.cmd("DEL").arg(key).ignore(),
+                // HSET and HDEL reject an empty list of fields
+                WriteOp::HSet(_, entries) if entries.is_empty() => continue,
+                WriteOp::HSet(key, entries) => pipe.cmd("HSET").arg(key).arg(entries).ignore(),
+                WriteOp::HDel(_, fields) if fields.is_empty() => continue,
+                WriteOp::HDel(key, fields) => pipe.cmd("HDEL").arg(key).arg(fields).ignore(),
+                WriteOp::Incr(key) => pipe.cmd("INCR").arg(key).ignore(),
+            };
+        }
+        let mut co = self.connect().await?;
+        let result: redis::RedisResult<()> = pipe.query_async(&mut co).await;
+        if let Err(err) = result {
+            tracing::error!("📕 Failed to commit transaction of {} writes: {}", ops.len(), err);
+            self.failure(&err);
+            return Err(StoreError::from(err));
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self, keys: Vec<String>, hash: Option<&str>) -> Result<(Vec<Option<Vec<u8>>>, Vec<Vec<u8>>), StoreError> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        pipe.cmd("MGET").arg(&keys);
+        if let Some(hash) = hash {
+            pipe.cmd("HVALS").arg(hash);
+        }
+        let mut co = self.connect().await?;
+        let result: redis::RedisResult<Vec<redis::Value>> = pipe.query_async(&mut co).await;
+        let replies = match result {
+            Ok(replies) => replies,
+            Err(err) => {
+                tracing::error!("📕 Failed to read snapshot of {:?}: {}", keys, err);
+                self.failure(&err);
+                return Err(StoreError::from(err));
+            }
+        };
+        let values = match replies.first() {
+            Some(reply) => redis::from_redis_value::<Vec<Option<Vec<u8>>>>(reply)?,
+            None => vec![],
+        };
+        let fields = match replies.get(1) {
+            Some(reply) => redis::from_redis_value::<Vec<Vec<u8>>>(reply)?,
+            None => vec![],
+        };
+        Ok((values, fields))
+    }
+
+    async fn xadd(&self, key: &str, maxlen: usize, fields: Vec<(String, String)>) -> Result<String, StoreError> {
+        let mut cmd = redis::cmd("XADD");
+        cmd.arg(key).arg("MAXLEN").arg("~").arg(maxlen).arg("*").arg(&fields);
+        self.query::<String>(&cmd, "append to stream", key).await
+    }
+
+    async fn xread(&self, key: &str, from: &str, count: usize) -> Result<Vec<(String, HashMap<String, String>)>, StoreError> {
+        let mut cmd = redis::cmd("XREAD");
+        cmd.arg("COUNT").arg(count).arg("STREAMS").arg(key).arg(from);
+        let reply = self.query::<StreamReadReply>(&cmd, "read stream", key).await?;
+        Ok(reply
+            .keys
+            .into_iter()
+            .flat_map(|x| x.ids)
+            .map(|x| {
+                let fields = x.map.iter().filter_map(|(k, v)| redis::from_redis_value::<String>(v).ok().map(|v| (k.clone(), v))).collect();
+                (x.id, fields)
+            })
+            .collect())
+    }
+
+    async fn publish(&self, channel: &str, message: String) -> Result<(), StoreError> {
+        let mut cmd = redis::cmd("PUBLISH");
+        cmd.arg(channel).arg(message);
+        self.query::<()>(&cmd, "publish on channel", channel).await
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<broadcast::Receiver<String>, StoreError> {
+        // Invalid endpoint, reported at once. Connection failures are retried by the background task
+        Client::open(self.endpoint.clone())?;
+        let (tx, rx) = broadcast::channel(SUBSCRIPTION_CAPACITY);
+        let (endpoint, channel) = (self.endpoint.clone(), channel.to_string());
+        tokio::spawn(async move {
+            // Pub/sub needs a dedicated connection, not handled by the connection manager
+            loop {
+                let pubsub = match Client::open(endpoint.clone()) {
+                    Ok(client) => client.get_async_pubsub().await,
+                    Err(e) => Err(e),
+                };
+                match pubsub {
+                    Ok(mut pubsub) => match pubsub.subscribe(channel.as_str()).await {
+                        Ok(_) => {
+                            tracing::debug!("📕 Subscribed to channel '{}'", channel);
+                            let mut messages = pubsub.on_message();
+                            while let Some(msg) = messages.next().await {
+                                match msg.get_payload::<String>() {
+                                    Ok(payload) => {
+                                        if tx.send(payload).is_err() {
+                                            tracing::debug!("📕 No receiver left on channel '{}', unsubscribing", channel);
+                                            return;
+                                        }
+                                    }
+                                    Err(e) => tracing::error!("📕 Invalid payload on channel '{}': {}", channel, e),
+                                }
+                            }
+                            tracing::warn!("📕 Subscription to channel '{}' ended, resubscribing", channel);
+                        }
+                        Err(e) => tracing::error!("📕 Failed to subscribe to channel '{}': {}", channel, e),
+                    },
+                    Err(e) => tracing::error!("📕 Failed to open pub/sub connection for channel '{}': {}", channel, e),
+                }
+                if tx.receiver_count() == 0 {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(REDIS_MAX_DELAY_MS)).await;
+            }
+        });
+        Ok(rx)
+    }
+
+    async fn status(&self) -> StoreHealth {
+        let time = std::time::Instant::now();
+        let pong: Result<String, RedisError> = match self.connect().await {
+            Ok(mut co) => redis::cmd("PING").query_async(&mut co).await,
+            Err(e) => Err(e),
+        };
+        let latency = time.elapsed().as_millis() as u64;
+        if let Err(e) = &pong {
+            self.failure(e);
+        }
+        let last = self.last_error.lock().ok().and_then(|x| x.clone());
+        StoreHealth {
+            backend: "redis".to_string(),
+            connected: pong.is_ok(),
+            latency_ms: latency,
+            failures: self.failures.load(Ordering::Relaxed),
+            last_error: last.clone().map(|x| x.1),
+            last_error_at: last.map(|x| x.0),
+        }
+    }
+}
+
+/// Entries of the in-memory streams, by stream key: id and fields of each entry, oldest first
+type Streams = HashMap<String, VecDeque<(String, HashMap<String, String>)>>;
+
+/// In-memory backend, to run the whole service in a single process (development, tests) without Redis
+#[derive(Default)]
+pub struct InMemoryStore {
+    data: RwLock<HashMap<String, Vec<u8>>>,
+    // Expiry of the keys set with set_ex
+    expiries: RwLock<HashMap<String, Instant>>,
+    hashes: RwLock<HashMap<String, HashMap<String, Vec<u8>>>>,
+    streams: RwLock<Streams>,
+    channels: RwLock<HashMap<String, broadcast::Sender<String>>>,
+}
+
+/// Order of two stream ids ('<ms>-<seq>'), ids missing a part being treated as 0
+fn idkey(id: &str) -> (u64, u64) {
+    let mut parts = id.split('-').map(|x| x.parse::<u64>().unwrap_or_default());
+    (parts.next().unwrap_or_default(), parts.next().unwrap_or_default())
+}
+
+#[async_trait]
+impl StateStore for InMemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        // Expired keys are dropped lazily, on read
+        let expired = self.expiries.read().await.get(key).map(|x| *x <= Instant::now()).unwrap_or(false);
+        if expired {
+            self.delete(key).await?;
+            return Ok(None);
+        }
+        Ok(self.data.read().await.get(key).cloned())
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>) -> Result<(), StoreError> {
+        self.data.write().await.insert(key.to_string(), value);
+        self.expiries.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn set_ex(&self, key: &str, value: Vec<u8>, ttl: u64) -> Result<(), StoreError> {
+        self.data.write().await.insert(key.to_string(), value);
+        self.expiries.write().await.insert(key.to_string(), Instant::now() + Duration::from_secs(ttl.max(1)));
+        Ok(())
+    }
+
+    async fn mset(&self, entries: Vec<(String, Vec<u8>)>) -> Result<(), StoreError> {
+        let mut data = self.data.write().await;
+        let mut expiries = self.expiries.write().await;
+        for (key, value) in entries {
+            expiries.remove(&key);
+            data.insert(key, value);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        self.data.write().await.remove(key);
+        self.expiries.write().await.remove(key);
+        self.hashes.write().await.remove(key);
+        self.streams.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn scan(&self, prefix: &str) -> Result<Vec<String>, StoreError> {
+        let mut keys = HashSet::new();
+        keys.extend(self.data.read().await.keys().filter(|x| x.starts_with(prefix)).cloned());
+        keys.extend(self.hashes.read().await.keys().filter(|x| x.starts_with(prefix)).cloned());
+        keys.extend(self.streams.read().await.keys().filter(|x| x.starts_with(prefix)).cloned());
+        Ok(keys.into_iter().collect())
+    }
+
+    async fn hset(&self, key: &str, entries: Vec<(String, Vec<u8>)>) -> Result<(), StoreError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut hashes = self.hashes.write().await;
+        let hash = hashes.entry(key.to_string()).or_default();
+        for (field, value) in entries {
+            hash.insert(field, value);
+        }
+        Ok(())
+    }
+
+    async fn hmget(&self, key: &str, fields: Vec<String>) -> Result<Vec<Option<Vec<u8>>>, StoreError> {
+        let hashes = self.hashes.read().await;
+        let hash = hashes.get(key);
+        Ok(fields.iter().map(|x| hash.and_then(|h| h.get(x).cloned())).collect())
+    }
+
+    async fn hdel(&self, key: &str, fields: Vec<String>) -> Result<(), StoreError> {
+        let mut hashes = self.hashes.write().await;
+        if let Some(hash) = hashes.get_mut(key) {
+            for field in fields.iter() {
+                hash.remove(field);
+            }
+            if hash.is_empty() {
+                hashes.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    async fn hvals(&self, key: &str) -> Result<Vec<Vec<u8>>, StoreError> {
+        Ok(self.hashes.read().await.get(key).map(|x| x.values().cloned().collect()).unwrap_or_default())
+    }
+
+    async fn transaction(&self, ops: Vec<WriteOp>) -> Result<(), StoreError> {
+        // All the locks are held while applying the writes, so readers see all of them or none
+        let mut data = self.data.write().await;
+        let mut expiries = self.expiries.write().await;
+        let mut hashes = self.hashes.write().await;
+        for op in ops {
+            match op {
+                WriteOp::Set(key, value) => {
+                    expiries.remove(&key);
+                    data.insert(key, value);
+                }
+                WriteOp::Delete(key) => {
+                    expiries.remove(&key);
+                    data.remove(&key);
+                    hashes.remove(&key);
+                }
+                WriteOp::HSet(key, entries) => {
+                    if !entries.is_empty() {
+                        hashes.entry(key).or_default().extend(entries);
+                    }
+                }
+                WriteOp::HDel(key, fields) => {
+                    if let Some(hash) = hashes.get_mut(&key) {
+                        for field in fields.iter() {
+                            hash.remove(field);
+                        }
+                        if hash.is_empty() {
+                            hashes.remove(&key);
+                        }
+                    }
+                }
+                WriteOp::Incr(key) => {
+                    // Stored as a plain integer, same as Redis
+                    let current = data.get(&key).and_then(|x| String::from_utf8(x.clone()).ok()).and_then(|x| x.parse::<u64>().ok()).unwrap_or_default();
+                    data.insert(key, (current + 1).to_string().into_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self, keys: Vec<String>, hash: Option<&str>) -> Result<(Vec<Option<Vec<u8>>>, Vec<Vec<u8>>), StoreError> {
+        let data = self.data.read().await;
+        let hashes = self.hashes.read().await;
+        let values = keys.iter().map(|x| data.get(x).cloned()).collect();
+        let fields = hash.and_then(|x| hashes.get(x)).map(|x| x.values().cloned().collect()).unwrap_or_default();
+        Ok((values, fields))
+    }
+
+    async fn xadd(&self, key: &str, maxlen: usize, fields: Vec<(String, String)>) -> Result<String, StoreError> {
+        let mut streams = self.streams.write().await;
+        let stream = streams.entry(key.to_string()).or_default();
+        // Same id format as Redis: milliseconds and a sequence number for entries added within the same millisecond
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|x| x.as_millis() as u64).unwrap_or_default();
+        let id = match stream.back().map(|x| idkey(&x.0)) {
+            Some((ms, seq)) if ms >= now => format!("{}-{}", ms, seq + 1),
+            _ => format!("{}-0", now),
+        };
+        stream.push_back((id.clone(), fields.into_iter().collect()));
+        while stream.len() > maxlen {
+            stream.pop_front();
+        }
+        Ok(id)
+    }
+
+    async fn xread(&self, key: &str, from: &str, count: usize) -> Result<Vec<(String, HashMap<String, String>)>, StoreError> {
+        let from = idkey(from);
+        Ok(match self.streams.read().await.get(key) {
+            Some(stream) => stream.iter().filter(|x| idkey(&x.0) > from).take(count).cloned().collect(),
+            None => vec![],
+        })
+    }
+
+    async fn publish(&self, channel: &str, message: String) -> Result<(), StoreError> {
+        if let Some(tx) = self.channels.read().await.get(channel) {
+            // No error if nobody is subscribed, same as Redis
+            let _ = tx.send(message);
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<broadcast::Receiver<String>, StoreError> {
+        let mut channels = self.channels.write().await;
+        Ok(channels.entry(channel.to_string()).or_insert_with(|| broadcast::channel(SUBSCRIPTION_CAPACITY).0).subscribe())
+    }
+
+    async fn status(&self) -> StoreHealth {
+        StoreHealth {
+            backend: "memory".to_string(),
+            connected: true,
+            latency_ms: 0,
+            failures: 0,
+            last_error: None,
+            last_error_at: None,
+        }
+    }
+}
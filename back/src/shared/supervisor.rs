@@ -0,0 +1,155 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{broadcast, watch};
+
+use crate::{
+    misc::r#static::METRICS_WINDOW,
+    types::{BlockEvent, RestartInfo, StreamMetrics, StreamState},
+};
+
+/// Lifecycle of the stream task of a network, recorded by the stream and its restart loop, reported on GET /{network}/stream
+/// Kept in memory, the API being served by the process running the streams
+pub struct Supervisor {
+    network: String,
+    launched: Instant,
+    tracker: Mutex<Tracker>,
+    // Generation of the stream inputs (e.g. token list), incremented when a restart is requested outside of the stream
+    reload: watch::Sender<u64>,
+    // Block events of the network, also notified of each state transition
+    events: broadcast::Sender<BlockEvent>,
+}
+
+/// One supervisor per network, shared by the stream task and the API
+pub type Supervisors = HashMap<String, Arc<Supervisor>>;
+
+struct Tracker {
+    state: StreamState,
+    since: Instant,
+    // Time spent in each past state (ms), by state name
+    durations: HashMap<String, u64>,
+    blocks: u64,
+    messages: u64,
+    // Latest block processed
+    latest: u64,
+    // Messages received within the last METRICS_WINDOW seconds
+    window: VecDeque<Instant>,
+    last_message: Option<Instant>,
+    restart: RestartInfo,
+}
+
+impl Supervisor {
+    pub fn new(network: String, events: broadcast::Sender<BlockEvent>) -> Self {
+        Supervisor {
+            network,
+            launched: Instant::now(),
+            reload: watch::Sender::new(0),
+            events,
+            tracker: Mutex::new(Tracker {
+                state: StreamState::Launching,
+                since: Instant::now(),
+                durations: HashMap::new(),
+                blocks: 0,
+                messages: 0,
+                latest: 0,
+                window: VecDeque::new(),
+                last_message: None,
+                restart: RestartInfo::default(),
+            }),
+        }
+    }
+
+    /// Record a state transition, the time spent in the previous state being accumulated. No-op if the state is unchanged
+    /// Subscribers of the block events are notified with the latest block and no component change, no error if nobody is listening
+    pub fn state(&self, state: StreamState) {
+        let latest = match self.tracker.lock() {
+            Ok(mut tracker) => {
+                if tracker.state.to_string() == state.to_string() {
+                    return;
+                }
+                let elapsed = tracker.since.elapsed().as_millis() as u64;
+                let previous = tracker.state.to_string();
+                *tracker.durations.entry(previous).or_default() += elapsed;
+                tracker.state = state.clone();
+                tracker.since = Instant::now();
+                tracker.latest
+            }
+            Err(_) => return,
+        };
+        let _ = self.events.send(BlockEvent {
+            block: latest,
+            updated: vec![],
+            added: vec![],
+            removed: vec![],
+            state,
+        });
+    }
+
+    /// Record a message received from the stream, with the block number if it was processed (not an error)
+    pub fn message(&self, block: Option<u64>) {
+        if let Ok(mut tracker) = self.tracker.lock() {
+            let now = Instant::now();
+            tracker.messages += 1;
+            if let Some(block) = block {
+                tracker.blocks += 1;
+                tracker.latest = block;
+            }
+            tracker.last_message = Some(now);
+            tracker.window.push_back(now);
+            while tracker.window.front().map(|x| now.duration_since(*x) > Duration::from_secs(METRICS_WINDOW)).unwrap_or(false) {
+                tracker.window.pop_front();
+            }
+        }
+    }
+
+    /// Record the restart state, after an exit of the stream task
+    pub fn restart(&self, info: RestartInfo) {
+        if let Ok(mut tracker) = self.tracker.lock() {
+            tracker.restart = info;
+        }
+    }
+
+    /// Ask the stream to end its session, to be restarted right away (e.g. with a new token list)
+    /// The generation is kept, so a request made while a session is being built is not lost
+    pub fn reload(&self) {
+        self.reload.send_modify(|x| *x += 1);
+    }
+
+    /// Current generation, to be read before the inputs of a session
+    pub fn generation(&self) -> u64 {
+        *self.reload.borrow()
+    }
+
+    /// Resolved once a restart was requested after the given generation, right away if it already was
+    pub async fn reloaded(&self, generation: u64) {
+        let mut receiver = self.reload.subscribe();
+        let _ = receiver.wait_for(|x| *x != generation).await;
+    }
+
+    /// Current metrics, the time spent in the current state included
+    pub fn report(&self) -> StreamMetrics {
+        let tracker = match self.tracker.lock() {
+            Ok(tracker) => tracker,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut durations = tracker.durations.clone();
+        *durations.entry(tracker.state.to_string()).or_default() += tracker.since.elapsed().as_millis() as u64;
+        let now = Instant::now();
+        let recent = tracker.window.iter().filter(|x| now.duration_since(**x) <= Duration::from_secs(METRICS_WINDOW)).count();
+        let span = self.launched.elapsed().as_secs_f64().clamp(1., METRICS_WINDOW as f64);
+        StreamMetrics {
+            network: self.network.clone(),
+            state: tracker.state.clone(),
+            uptime_ms: self.launched.elapsed().as_millis() as u64,
+            states_ms: durations,
+            blocks: tracker.blocks,
+            messages: tracker.messages,
+            messages_per_second: recent as f64 / span,
+            since_last_message_ms: tracker.last_message.map(|x| x.elapsed().as_millis() as u64),
+            restart: tracker.restart.clone(),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use tycho_orderbook::types::TychoStreamState;
+use tycho_simulation::protocol::models::{BlockUpdate, ProtocolComponent};
+
+use crate::types::ComponentEntry;
+
+/// Replace the shared state with the first BlockUpdate of a session, which carries every component and state
+/// Both maps are keyed by lowercased component id
+pub fn init(state: &mut TychoStreamState, msg: &BlockUpdate) {
+    state.protosims = msg.states.iter().map(|(id, x)| (id.to_lowercase(), x.clone())).collect();
+    state.components = msg.new_pairs.iter().map(|(id, x)| (id.to_lowercase(), x.clone())).collect();
+    state.initialised = true;
+}
+
+/// Fill the components of the shared state from a checkpoint, so that /execute works while the stream syncs
+/// Protosims can't be checkpointed, the state stays uninitialised until the first BlockUpdate replaces everything
+pub fn restore(state: &mut TychoStreamState, components: &[ComponentEntry]) {
+    state.components = components.iter().map(|x| (x.component.id.to_lowercase(), ProtocolComponent::from(x.component.clone()))).collect();
+}
+
+/// Component of the shared state, whatever the casing of the id
+pub fn component<'a>(state: &'a TychoStreamState, id: &str) -> Option<&'a ProtocolComponent> {
+    state.components.get(&id.to_lowercase())
+}
+
+/// Apply a BlockUpdate to the shared state: updated states, new and removed pairs, so that protosims and components stay in sync (components used by /execute)
+/// Returns the lowercased ids of the updated states
+pub fn apply(state: &mut TychoStreamState, msg: &BlockUpdate) -> Vec<String> {
+    let mut updated = Vec::with_capacity(msg.states.len());
+    for (id, protosim) in msg.states.iter() {
+        let id = id.to_lowercase();
+        state.protosims.insert(id.clone(), protosim.clone());
+        updated.push(id);
+    }
+    for (id, component) in msg.new_pairs.iter() {
+        state.components.insert(id.to_lowercase(), component.clone());
+    }
+    for id in msg.removed_pairs.keys() {
+        let id = id.to_lowercase();
+        state.components.remove(&id);
+        state.protosims.remove(&id);
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use alloy_primitives::U256;
+    use tycho_orderbook::types::TychoStreamState;
+    use tycho_simulation::{
+        evm::protocol::uniswap_v2::state::UniswapV2State,
+        protocol::{
+            models::{BlockUpdate, ProtocolComponent},
+            state::ProtocolSim,
+        },
+        tycho_core::{dto::Chain, Bytes},
+    };
+
+    use super::{apply, component, init};
+
+    fn component(id: &str) -> ProtocolComponent {
+        ProtocolComponent {
+            address: Bytes::from(id),
+            id: Bytes::from(id),
+            tokens: vec![],
+            protocol_system: "uniswap_v2".to_string(),
+            protocol_type_name: "uniswap_v2_pool".to_string(),
+            chain: Chain::Ethereum,
+            contract_ids: vec![],
+            static_attributes: HashMap::new(),
+            creation_tx: Bytes::default(),
+            created_at: Default::default(),
+        }
+    }
+
+    fn protosim(reserve: u64) -> Box<dyn ProtocolSim> {
+        Box::new(UniswapV2State::new(U256::from(reserve), U256::from(reserve)))
+    }
+
+    fn reserve(state: &TychoStreamState, id: &str) -> Option<U256> {
+        state.protosims.get(id).and_then(|x| x.as_any().downcast_ref::<UniswapV2State>()).map(|x| x.reserve0)
+    }
+
+    // Scripted BlockUpdate: (id, reserve) states, new pairs, removed pairs
+    fn block(number: u64, states: &[(&str, u64)], added: &[&str], removed: &[&str]) -> BlockUpdate {
+        BlockUpdate {
+            block_number: number,
+            states: states.iter().map(|(id, x)| (id.to_string(), protosim(*x))).collect(),
+            new_pairs: added.iter().map(|id| (id.to_string(), component(id))).collect(),
+            removed_pairs: removed.iter().map(|id| (id.to_string(), component(id))).collect(),
+        }
+    }
+
+    fn empty() -> TychoStreamState {
+        TychoStreamState {
+            protosims: HashMap::new(),
+            components: HashMap::new(),
+            initialised: false,
+        }
+    }
+
+    const A: &str = "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    #[test]
+    fn pair_added_then_updated() {
+        let mut state = empty();
+        init(&mut state, &block(1, &[(A, 10)], &[A], &[]));
+        assert!(state.initialised);
+        let updated = apply(&mut state, &block(2, &[(B, 20)], &[B], &[]));
+        assert_eq!(updated, vec![B.to_string()]);
+        assert!(state.components.contains_key(B));
+        assert_eq!(reserve(&state, B), Some(U256::from(20)));
+        let updated = apply(&mut state, &block(3, &[(B, 30)], &[], &[]));
+        assert_eq!(updated, vec![B.to_string()]);
+        assert_eq!(reserve(&state, B), Some(U256::from(30)));
+        assert_eq!(reserve(&state, A), Some(U256::from(10)));
+        assert_eq!(state.components.len(), 2);
+    }
+
+    #[test]
+    fn pair_removed() {
+        let mut state = empty();
+        init(&mut state, &block(1, &[(A, 10), (B, 20)], &[A, B], &[]));
+        let updated = apply(&mut state, &block(2, &[], &[], &[A]));
+        assert!(updated.is_empty());
+        assert!(!state.components.contains_key(A));
+        assert!(!state.protosims.contains_key(A));
+        assert!(state.components.contains_key(B));
+        assert!(state.protosims.contains_key(B));
+    }
+
+    #[test]
+    fn mixed_case_ids() {
+        let upper = "0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+        let mut state = empty();
+        init(&mut state, &block(1, &[(upper, 10)], &[upper], &[]));
+        assert!(state.components.contains_key(A));
+        assert!(state.protosims.contains_key(A));
+        let updated = apply(&mut state, &block(2, &[(upper, 15)], &[], &[]));
+        assert_eq!(updated, vec![A.to_string()]);
+        assert_eq!(reserve(&state, A), Some(U256::from(15)));
+        assert_eq!(state.protosims.len(), 1);
+        // Looked up with another casing than the stored one, e.g. the ids of an /execute request
+        assert_eq!(component(&state, upper).map(|x| x.id.clone()), Some(Bytes::from(upper)));
+        assert!(component(&state, A).is_some());
+        // Removed with another casing than the one it was added with
+        apply(&mut state, &block(3, &[], &[], &[A]));
+        assert!(state.components.is_empty());
+        assert!(state.protosims.is_empty());
+    }
+}
@@ -1,11 +1,20 @@
-use std::fmt::{self, Display};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
 
+use axum::http::StatusCode;
 use serde::{Deserialize, Serialize};
-use tycho_orderbook::types::ExecutionRequest;
-use utoipa::ToSchema;
+use tycho_orderbook::{
+    data::fmt::{SrzProtocolComponent, SrzToken},
+    types::{ExecutionRequest, Network, Orderbook, OrderbookRequestParams},
+};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::store::StoreError;
 
 /// Used to safely progress with Redis database
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub enum StreamState {
     Down = 1,
     Launching = 2,
@@ -39,16 +48,168 @@ pub struct ExecutionContext {
 pub struct APIResponse<T = String> {
     pub success: bool,
     pub error: String,
+    // Machine-readable error code (see ApiError), only set on failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "unknown_token")]
+    pub code: Option<String>,
     pub ts: u64,
     pub data: Option<T>,
 }
 
+/// Typed API error, each variant maps to an HTTP status code and a stable machine-readable code
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum ApiError {
+    // Missing or invalid API key header
+    Unauthorized(String),
+    // Stream not yet synchronised, nothing to serve
+    NotInitialised(String),
+    // Malformed request (e.g. tag not formatted as 0xt0-0xt1)
+    BadRequest(String),
+    // Token not listed by Tycho on the network
+    UnknownToken(String),
+    // Component not (or no longer) streamed on the network
+    UnknownComponent(String),
+    // Symbol matching several tokens, message lists the candidate addresses
+    AmbiguousSymbol(String),
+    // No liquidity pool for the requested pair
+    NoPools(String),
+    // No path found between tokens
+    RoutingFailed(String),
+    // Orderbook or quote simulation failed
+    SimulationFailed(String),
+    // Transaction building failed
+    ExecutionFailed(String),
+    // Redis read/write failure
+    Storage(String),
+    // Stored value unreadable (codec mismatch, schema drift)
+    CorruptedData(String),
+}
+
+impl ApiError {
+    /// HTTP status code returned for this error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::NotInitialised(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::UnknownToken(_) => StatusCode::NOT_FOUND,
+            ApiError::UnknownComponent(_) => StatusCode::NOT_FOUND,
+            ApiError::AmbiguousSymbol(_) => StatusCode::CONFLICT,
+            ApiError::NoPools(_) => StatusCode::NOT_FOUND,
+            ApiError::RoutingFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::SimulationFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::ExecutionFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Storage(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::CorruptedData(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable machine-readable code, same as the serialized tag
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::NotInitialised(_) => "not_initialised",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::UnknownToken(_) => "unknown_token",
+            ApiError::UnknownComponent(_) => "unknown_component",
+            ApiError::AmbiguousSymbol(_) => "ambiguous_symbol",
+            ApiError::NoPools(_) => "no_pools",
+            ApiError::RoutingFailed(_) => "routing_failed",
+            ApiError::SimulationFailed(_) => "simulation_failed",
+            ApiError::ExecutionFailed(_) => "execution_failed",
+            ApiError::Storage(_) => "storage",
+            ApiError::CorruptedData(_) => "corrupted_data",
+        }
+    }
+
+    /// Human readable message
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::Unauthorized(msg)
+            | ApiError::NotInitialised(msg)
+            | ApiError::BadRequest(msg)
+            | ApiError::UnknownToken(msg)
+            | ApiError::UnknownComponent(msg)
+            | ApiError::AmbiguousSymbol(msg)
+            | ApiError::NoPools(msg)
+            | ApiError::RoutingFailed(msg)
+            | ApiError::SimulationFailed(msg)
+            | ApiError::ExecutionFailed(msg)
+            | ApiError::Storage(msg)
+            | ApiError::CorruptedData(msg) => msg.clone(),
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(e: StoreError) -> Self {
+        match e {
+            // The stream hasn't written the value yet (launching, or network not synced)
+            StoreError::NotFound(_) => ApiError::NotInitialised(format!("Stream data not available yet: {}", e)),
+            StoreError::Connection(_) => ApiError::Storage(e.to_string()),
+            StoreError::Encode(_) | StoreError::Decode(_) => ApiError::CorruptedData(e.to_string()),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Status {
     #[schema(example = "4")]
     pub stream: u128, // StreamState
     #[schema(example = "22051447")]
     pub latest: String,
+    // Version of the per-block snapshot the status and latest block were read from
+    #[schema(example = "1204")]
+    pub snapshot: u64,
+    // True while the data served was restored from a checkpoint (or left by a previous session) and the stream is not yet running
+    // Tokens, components and pairs are served from the checkpoint, but protosims aren't checkpointed: simulations (orderbook, quote) wait for the first block
+    pub stale: bool,
+    // Restart state of the stream task, None until its first exit
+    pub restart: Option<RestartInfo>,
+    // Health of the store (Redis connection)
+    pub store: StoreHealth,
+}
+
+/// Per-block keys of a network read at once (stream:snapshot:<network>), all written by the same block transaction
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Snapshot {
+    // Incremented by each block transaction
+    #[schema(example = "1204")]
+    pub version: u64,
+    #[schema(example = "22051447")]
+    pub block: u64,
+    #[schema(example = "4")]
+    pub stream: u128, // StreamState
+    // Components updated at this block
+    pub updated: Vec<String>,
+}
+
+impl Snapshot {
+    /// True if the data was restored from a checkpoint and the stream is not running again yet
+    pub fn stale(&self) -> bool {
+        self.stream != StreamState::Running as u128 && self.block > 0
+    }
+}
+
+/// Health of the store backend
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StoreHealth {
+    // 'redis' or 'memory'
+    pub backend: String,
+    pub connected: bool,
+    // PING round trip
+    pub latency_ms: u64,
+    // Number of store errors since launch
+    pub failures: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<u64>,
 }
 
 // A simple structure for the API version.
@@ -59,7 +220,7 @@ pub struct Version {
 }
 
 // A simple structure for the API version.
-#[derive(Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PairTag {
     #[schema(example = "ETH")]
     pub base: String,
@@ -71,6 +232,322 @@ pub struct PairTag {
     pub addrquote: String,
 }
 
+/// Query params of the orderbook WebSocket, tag being "0xt0-0xt1"
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+pub struct OrderbookStreamParams {
+    // Base and quote token addresses, or symbols
+    #[param(example = "WETH-USDC")]
+    pub tag: String,
+}
+
+/// Query params shared by list endpoints (/components, /tokens, /pairs), all optional
+/// Filters not relevant for an endpoint are ignored, sort accepts a '-' prefix for descending order
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct ListParams {
+    // Components only, and pairs (built from the filtered components)
+    #[param(example = "uniswap_v3")]
+    pub protocol_system: Option<String>,
+    // Token address contained in the item
+    #[param(example = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")]
+    pub token: Option<String>,
+    // Token symbol contained in the item, case insensitive
+    #[param(example = "WETH")]
+    pub symbol: Option<String>,
+    // Components only, and pairs: components updated at or after this timestamp
+    pub updated_since: Option<u64>,
+    // Components: id, protocol, updated | Tokens: address, symbol | Pairs: base, quote. Defaults to id (tokens and pairs: address)
+    #[param(example = "-updated")]
+    pub sort: Option<String>,
+    pub limit: Option<usize>,
+    // Opaque cursor, returned as 'next' by the previous page
+    pub cursor: Option<String>,
+}
+
+/// One page of a list endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // Number of items matching the filters, across all pages
+    pub total: usize,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    // Cursor of the next page, none if this is the last one
+    pub next: Option<String>,
+    // True while serving data restored from a checkpoint, before the stream is running again
+    pub stale: bool,
+}
+
+/// Value of each field of the components hash, written by the stream at each update of the component
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ComponentEntry {
+    // Block of the last state update
+    pub block: u64,
+    pub component: SrzProtocolComponent,
+}
+
+/// Spot price of base in quote, for one direction of a component
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SpotPrice {
+    pub base: String,
+    pub quote: String,
+    pub price: f64,
+}
+
+/// Component with its live state, derived from its protosim
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ComponentDetail {
+    pub component: SrzProtocolComponent,
+    // Block and timestamp of the last state update
+    pub block: u64,
+    pub timestamp: u64,
+    pub fee: f64,
+    // Spot prices for each ordered pair of tokens of the component
+    pub prices: Vec<SpotPrice>,
+}
+
+/// Query params of the quote endpoint, amount being in human readable units of the sell token
+#[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
+pub struct QuoteParams {
+    #[param(example = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2")]
+    pub sell: String,
+    #[param(example = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48")]
+    pub buy: String,
+    #[param(example = 3.0)]
+    pub amount: f64,
+}
+
+/// Part of a quote routed through one pool
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuoteSplit {
+    pub component: String,
+    pub protocol: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+}
+
+/// Quote of a single amount, split across pools
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Quote {
+    pub sell: String,
+    pub buy: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    // Effective price, in buy token per sell token
+    pub price: f64,
+    // Best spot price among pools
+    pub mid: f64,
+    // Price impact versus mid, in percent
+    pub impact: f64,
+    pub split: Vec<QuoteSplit>,
+    #[schema(example = "22051447")]
+    pub block: u64,
+}
+
+/// Orderbook request, with the max age accepted for a cached orderbook
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OrderbookQuery {
+    #[serde(flatten)]
+    pub params: OrderbookRequestParams,
+    // Max age (seconds) of a cached orderbook, 0 to force a new computation. Defaults to the cache TTL
+    #[schema(example = "60")]
+    pub max_age: Option<u64>,
+}
+
+/// Orderbook, along with its cache status
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CachedOrderbook {
+    #[serde(flatten)]
+    pub orderbook: Orderbook,
+    // True if served from the cache
+    pub cached: bool,
+    // Seconds since the orderbook was computed
+    pub age: u64,
+    // True if computed before a restart, served while the stream is not yet running
+    pub stale: bool,
+}
+
+/// Orderbook of a pair, tagged by kind: simulated over the pools having both tokens, or through intermediate tokens when there is none
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum OrderbookResponse {
+    Direct(CachedOrderbook),
+    Multihop(MultiHopOrderbook),
+}
+
+/// Result of a batch of orderbook requests, by tag
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BatchOrderbooks {
+    pub orderbooks: HashMap<String, OrderbookResponse>,
+    pub errors: HashMap<String, ApiError>,
+}
+
+/// One hop of a multi-hop route, swapping input for output through a single component
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RouteHop {
+    #[schema(example = "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640")]
+    pub component: String,
+    #[schema(example = "uniswap_v3")]
+    pub protocol: String,
+    pub input: String,
+    pub output: String,
+}
+
+/// One level of a synthetic multi-hop orderbook
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HopLevel {
+    // Amount sold (base for bids, quote for asks)
+    pub amount: f64,
+    // Amount received (quote for bids, base for asks)
+    pub output: f64,
+    // Average price, in quote per base
+    pub price: f64,
+    // Components used by the level, in swap order
+    pub hops: Vec<String>,
+}
+
+/// Synthetic orderbook for a pair without direct pools, simulated through intermediate tokens
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MultiHopOrderbook {
+    #[schema(example = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2-0x8e870d67f660d95d5be530380d0ec0bd388289e1")]
+    pub tag: String,
+    pub block: u64,
+    pub timestamp: u64,
+    pub base: SrzToken,
+    pub quote: SrzToken,
+    // Spot price along the base to quote route, in quote per base
+    pub mid: f64,
+    // Route used to sell base (bids) and to sell quote (asks)
+    pub route_bids: Vec<RouteHop>,
+    pub route_asks: Vec<RouteHop>,
+    pub bids: Vec<HopLevel>,
+    pub asks: Vec<HopLevel>,
+}
+
+/// Event emitted for each BlockUpdate processed by the stream, and for each transition of the stream state (latest block, no component change)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BlockEvent {
+    #[schema(example = "22051447")]
+    pub block: u64,
+    // Components ids with state updates
+    pub updated: Vec<String>,
+    // Components added at this block
+    pub added: Vec<SrzProtocolComponent>,
+    // Components ids removed at this block
+    pub removed: Vec<String>,
+    pub state: StreamState,
+}
+
+/// Entry of the block journal (stream:journal:<network>), compact version of BlockEvent with components ids only
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JournalEntry {
+    // Redis stream id, to resume reading after this entry
+    #[schema(example = "1742462400000-0")]
+    pub id: String,
+    #[schema(example = "22051447")]
+    pub block: u64,
+    pub updated: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Restart state of the stream task of a network (stream:restart:<network>)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct RestartInfo {
+    // Consecutive failures, 0 once a session ended cleanly or ran long enough
+    pub attempt: u32,
+    // Restarts since launch, after a clean end or a failure of the stream
+    pub restarts: u64,
+    // Restarts since launch requested by a token list refresh, not counted in restarts
+    pub reloads: u64,
+    // Timestamp of the next restart, None while the stream is running
+    pub next_retry_at: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<u64>,
+}
+
+/// Filters applied to the stream of a network (STREAM_FILTER_<NETWORK> or STREAM_FILTER), all unset by default
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct StreamFilters {
+    // Protocol systems streamed, all if empty
+    #[schema(example = json!(["uniswap_v2", "uniswap_v3"]))]
+    pub allow: Vec<String>,
+    // Protocol systems never streamed
+    pub deny: Vec<String>,
+    // Min TVL of the streamed components, in the native token of the network
+    pub min_tvl: Option<f64>,
+    // Min Tycho quality of the tokens (100 = no rebasing, fee-on-transfer, etc.)
+    pub min_token_quality: Option<i32>,
+    // Tokens not traded for longer are ignored
+    pub max_days_since_last_trade: Option<u64>,
+}
+
+/// Network, along with the filters applied to its stream
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NetworkConfig {
+    #[serde(flatten)]
+    pub network: Network,
+    pub filters: StreamFilters,
+}
+
+/// Lifecycle metrics of the stream task of a network, since launch
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StreamMetrics {
+    pub network: String,
+    pub state: StreamState,
+    pub uptime_ms: u64,
+    // Time spent in each StreamState, by state name
+    pub states_ms: HashMap<String, u64>,
+    // BlockUpdate processed, all sessions included
+    pub blocks: u64,
+    // Messages received (blocks and errors)
+    pub messages: u64,
+    // Over the last METRICS_WINDOW seconds
+    pub messages_per_second: f64,
+    // None until the first message
+    pub since_last_message_ms: Option<u64>,
+    pub restart: RestartInfo,
+}
+
+/// Checkpoint of the streamed data of a network (stream:checkpoint:<network>), restored at launch to be served (stale) while the stream syncs
+/// Protosims are trait objects without serde support in tycho-simulation and can't be checkpointed, simulations wait for the live stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub block: u64,
+    pub timestamp: u64,
+    pub tokens: Vec<SrzToken>,
+    pub components: Vec<ComponentEntry>,
+}
+
+/// Change of the token list of a network, entry of the new tokens feed (stream:tokens:feed:<network>)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenDiff {
+    // Redis stream id, to resume reading after this entry
+    #[schema(example = "1742462400000-0")]
+    pub id: String,
+    pub timestamp: u64,
+    pub added: Vec<SrzToken>,
+    // Addresses of the tokens no longer listed
+    pub removed: Vec<String>,
+}
+
+/// Query params of the feeds, both optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+pub struct FeedParams {
+    // Id of the last entry already read, entries are returned strictly after it. From the oldest entry kept by default
+    #[param(example = "1742462400000-0")]
+    pub from: Option<String>,
+    // Max number of entries returned, 100 by default
+    pub count: Option<usize>,
+}
+
+/// Pub/sub message sent by the stream at each block, listing the components whose cached orderbooks are outdated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invalidation {
+    pub block: u64,
+    // Components ids updated or removed at this block
+    pub components: Vec<String>,
+}
+
 /// Environment configuration expected
 #[derive(Debug, Clone)]
 pub struct EnvAPIConfig {
@@ -88,4 +565,12 @@ pub struct EnvAPIConfig {
     pub web_api_key: String,
     // Header API key for tycho-web
     pub api_port: String,
+    // TTL (seconds) of cached orderbooks, CACHE_OB_DURATION by default
+    pub cache_ttl: u64,
+    // True to delete the unprefixed keys of schema v1 when migrating (DROP_LEGACY_KEYS), kept by default as they may belong to another deployment
+    pub drop_legacy_keys: bool,
+    // Delay (seconds) between two checkpoints of the streamed data, CHECKPOINT_INTERVAL by default, 0 to disable
+    pub checkpoint_interval: u64,
+    // Delay (seconds) between two refreshes of the Tycho tokens, TOKENS_REFRESH_INTERVAL by default, 0 to disable
+    pub tokens_refresh: u64,
 }
@@ -1,15 +1,25 @@
 use futures::FutureExt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use tycho_orderbook::data::fmt::SrzToken;
 
 use futures::StreamExt;
 use shared::data::keys;
-use shared::getters;
-use shared::misc::r#static::RESTART_STREAM_DELAY;
+use shared::misc::r#static::BLOCK_EVENTS_CAPACITY;
+use shared::restart::RestartPolicy;
+use shared::store::Store;
+use shared::supervisor::{Supervisor, Supervisors};
+use shared::types::BlockEvent;
+use shared::types::Checkpoint;
+use shared::types::ComponentEntry;
 use shared::types::EnvAPIConfig;
+use shared::types::Invalidation;
+use shared::types::RestartInfo;
+use shared::types::StreamFilters;
 use shared::types::StreamState;
+use std::str::FromStr;
+use tokio::sync::broadcast;
 use tokio::sync::RwLock;
 use tycho_orderbook::builder::OrderbookBuilder;
 use tycho_orderbook::core::client;
@@ -19,35 +29,79 @@ use tycho_orderbook::types::SharedTychoStreamState;
 use tycho_orderbook::types::TychoStreamState;
 use tycho_orderbook::utils::misc::current_timestamp;
 use tycho_orderbook::utils::r#static::filter;
+use tycho_simulation::evm::tycho_models::Chain;
 use tycho_simulation::models::Token;
+use tycho_simulation::utils::load_all_tokens;
 
 pub mod axum;
 
 /// Stream the entire state from each AMMs, with TychoStreamBuilder.
 /// Note: a single connection attempt is made, and if it ends (even due to an error) the function returns, the main loop will handle re-calling stream
+/// Returns Ok on a clean end of stream (restarted after the base delay, right away on a reload), Err with the reason otherwise (restarted with backoff)
 /// Other code example: https://github.com/dewiz-xyz/tycho-simulation-ts/blob/master/src/lib.rs
-async fn stream(network: Network, cache: SharedTychoStreamState, config: EnvAPIConfig, tokens: Vec<Token>) {
+async fn stream(
+    network: Network,
+    cache: SharedTychoStreamState,
+    store: Store,
+    events: broadcast::Sender<BlockEvent>,
+    config: EnvAPIConfig,
+    tokens: Vec<Token>,
+    supervisor: Arc<Supervisor>,
+    generation: u64,
+) -> Result<(), String> {
     tracing::debug!("Connecting ProtocolStreamBuilder task for {} with {} tokens", network.name, tokens.len());
+    supervisor.state(StreamState::Launching);
     let srztokens = tokens.iter().map(|t| SrzToken::from(t.clone())).collect::<Vec<_>>();
     let key = keys::stream::tokens(network.name.clone());
-    shared::data::set(key.as_str(), srztokens.clone()).await;
-    let obb = OrderbookBuilder::new(network.clone(), None, config.tycho_api_key.clone(), tokens.clone()).await;
+    let _ = shared::data::set(&store, key.as_str(), srztokens.clone()).await;
+    let filters = StreamFilters::from_env(network.name.as_str());
+    tracing::debug!("Stream filters for {}: {:?}", network.name, filters);
+    let obb = OrderbookBuilder::new(network.clone(), filters.builder(), config.tycho_api_key.clone(), tokens.clone()).await;
     let stream = obb.psb.build().await;
     if stream.is_err() {
         let err = stream.err().unwrap();
         tracing::warn!("Failed to build stream on {}: {:?}. Exiting.", network.name, err.to_string());
         // Set error state before returning.
-        shared::data::set(keys::stream::status(network.name.clone()).as_str(), StreamState::Error as u128).await;
-        return;
+        let _ = shared::data::set(&store, keys::stream::status(network.name.clone()).as_str(), StreamState::Error as u128).await;
+        supervisor.state(StreamState::Error);
+        return Err(format!("Failed to build stream: {}", err));
     }
     {
         // Use a block so that the stream is dropped at the end, just to ensure the connection is closed, but not it's necessary.
         let mut stream = stream.unwrap();
+        let mut checkpointed = std::time::Instant::now();
+        // Resolved by any restart request since the inputs of the session were read, including during the build
+        let reloaded = supervisor.reloaded(generation);
+        tokio::pin!(reloaded);
         loop {
-            match stream.next().await {
+            let next = tokio::select! {
+                msg = stream.next() => msg,
+                _ = &mut reloaded => {
+                    tracing::info!("Restart of the stream requested on {}. Exiting stream session.", network.name);
+                    return Ok(());
+                }
+            };
+            match next {
                 Some(msg) => {
                     match msg {
-                        Ok(msg) => {
+                        Ok(mut msg) => {
+                            supervisor.message(Some(msg.block_number));
+                            if filters.restricts() {
+                                // Components of excluded protocol systems are dropped, along with their states
+                                let excluded = msg
+                                    .new_pairs
+                                    .iter()
+                                    .chain(msg.removed_pairs.iter())
+                                    .filter(|(_, comp)| !filters.allows(comp.protocol_system.as_str()))
+                                    .map(|(id, _)| id.clone())
+                                    .collect::<HashSet<String>>();
+                                msg.new_pairs.retain(|id, _| !excluded.contains(id));
+                                msg.removed_pairs.retain(|id, _| !excluded.contains(id));
+                                // States of components excluded in a previous block are not in the shared state either
+                                let known = cache.read().await;
+                                msg.states.retain(|id, _| msg.new_pairs.contains_key(id) || known.components.contains_key(&id.to_lowercase()));
+                                drop(known);
+                            }
                             tracing::info!(
                                 "{} '{}' stream: block # {} with {} states updates, + {} pairs, - {} pairs",
                                 network.tag.clone(),
@@ -57,22 +111,20 @@ async fn stream(network: Network, cache: SharedTychoStreamState, config: EnvAPIC
                                 msg.new_pairs.len(),
                                 msg.removed_pairs.len()
                             );
-                            shared::data::set(keys::stream::latest(network.name.clone()).as_str(), msg.block_number).await;
                             let mtx = cache.read().await;
                             let initialised = mtx.initialised;
                             drop(mtx);
                             if !initialised {
                                 tracing::info!("First stream (= uninitialised). Writing the entire streamed data into the TychoStreamState shared struct.");
-                                shared::data::set(keys::stream::status(network.name.clone()).as_str(), StreamState::Syncing as u128).await;
+                                let _ = shared::data::set(&store, keys::stream::status(network.name.clone()).as_str(), StreamState::Syncing as u128).await;
+                                supervisor.state(StreamState::Syncing);
                                 // ===== Update Shared State at first sync only =====
                                 let mut targets = vec![];
                                 for (_id, comp) in msg.new_pairs.iter() {
                                     targets.push(comp.id.to_string().to_lowercase());
                                 }
                                 let mut mtx = cache.write().await;
-                                mtx.protosims = msg.states.clone();
-                                mtx.components = msg.new_pairs.clone();
-                                mtx.initialised = true;
+                                shared::sync::init(&mut mtx, &msg);
                                 drop(mtx);
                                 let mut components = vec![];
                                 for m in targets.clone() {
@@ -85,74 +137,100 @@ async fn stream(network: Network, cache: SharedTychoStreamState, config: EnvAPIC
                                         components.push(SrzProtocolComponent::from(comp.clone()));
                                     }
                                 }
-                                // ===== Storing ALL components =====
+                                // ===== Storing ALL components, one hash field per component, with the block keys in a single transaction =====
                                 tracing::debug!("Storing {} components on {}", components.len(), network.name);
                                 let key = keys::stream::components(network.name.clone());
-                                shared::data::set(key.as_str(), components.clone()).await;
-                                let key = keys::stream::updated(network.name.clone());
-                                shared::data::set::<Vec<String>>(key.as_str(), vec![]).await;
+                                let entries = components
+                                    .iter()
+                                    .map(|x| {
+                                        (
+                                            x.id.to_lowercase(),
+                                            ComponentEntry {
+                                                block: msg.block_number,
+                                                component: x.clone(),
+                                            },
+                                        )
+                                    })
+                                    .collect::<Vec<_>>();
                                 // ===== Set StreamState to up and running =====
-                                shared::data::set(keys::stream::status(network.name.clone()).as_str(), StreamState::Running as u128).await;
+                                let mut tx = shared::data::block(network.name.clone(), msg.block_number, StreamState::Running, vec![]);
+                                // Start from a clean hash, dropping components of a previous session
+                                tx.delete(key.as_str()).hset(key.as_str(), entries);
+                                let _ = shared::data::commit(&store, tx).await;
                                 tracing::info!("✅ Proto Stream initialised successfully. StreamState set to 'Running' on {}", network.name.clone());
                             } else {
                                 // ===== Update Shared State =====
                                 // tracing::trace!("Stream already initialised. Updating the mutex-shared state with new data, and updating Redis.");
                                 let mut components_to_update = vec![];
-                                if !msg.states.is_empty() {
+                                if !msg.states.is_empty() || !msg.new_pairs.is_empty() || !msg.removed_pairs.is_empty() {
                                     let mut mtx = cache.write().await;
-                                    let cpids = msg.states.keys().map(|x| x.clone().to_lowercase()).collect::<Vec<String>>();
-                                    for x in msg.states.iter() {
-                                        mtx.protosims.insert(x.0.clone().to_lowercase(), x.1.clone());
-                                        components_to_update.push(x.0.clone().to_lowercase());
-                                    }
-                                    let key = keys::stream::updated(network.name.clone());
-                                    shared::data::set::<Vec<String>>(key.as_str(), cpids.clone()).await;
+                                    components_to_update = shared::sync::apply(&mut mtx, &msg);
                                     drop(mtx);
                                 }
 
-                                if !components_to_update.is_empty() || !msg.new_pairs.is_empty() || !msg.removed_pairs.is_empty() {
-                                    match getters::components(network.clone()).await {
-                                        Some(mut components) => {
-                                            let timestamp = current_timestamp();
-                                            for x in components_to_update.iter() {
-                                                if let Some(pos) = components.iter().position(|current| current.id.to_string().to_lowercase() == x.to_string().to_lowercase()) {
-                                                    components[pos].last_updated_at = timestamp;
-                                                }
-                                            }
-                                            for x in msg.new_pairs.iter() {
-                                                let pc = SrzProtocolComponent::from(x.1.clone());
-                                                if let Some(pos) = components.iter().position(|current| current.id.to_string().to_lowercase() == x.0.to_string().to_lowercase()) {
-                                                    components[pos] = pc;
-                                                } else {
-                                                    components.push(pc);
-                                                }
-                                            }
-                                            for x in msg.removed_pairs.iter() {
-                                                if let Some(pos) = components.iter().position(|current| current.id.to_string().to_lowercase() == x.0.to_string().to_lowercase()) {
-                                                    components.swap_remove(pos);
-                                                }
-                                            }
-                                            let key = keys::stream::components(network.name.clone());
-                                            shared::data::set(key.as_str(), components.clone()).await;
-                                        }
-                                        None => {
-                                            tracing::error!("Failed to get components. Exiting.");
-                                        }
+                                // ===== Touch only the affected fields of the components hash =====
+                                let key = keys::stream::components(network.name.clone());
+                                let mut entries = vec![];
+                                if !components_to_update.is_empty() {
+                                    let timestamp = current_timestamp();
+                                    let updated = shared::data::hmget::<ComponentEntry>(&store, key.as_str(), components_to_update.clone()).await.unwrap_or_default();
+                                    for mut entry in updated {
+                                        entry.block = msg.block_number;
+                                        entry.component.last_updated_at = timestamp;
+                                        entries.push((entry.component.id.to_lowercase(), entry));
                                     }
                                 }
-                                shared::data::set(keys::stream::status(network.name.clone()).as_str(), StreamState::Running as u128).await;
+                                for x in msg.new_pairs.values() {
+                                    let component = SrzProtocolComponent::from(x.clone());
+                                    let entry = ComponentEntry { block: msg.block_number, component };
+                                    entries.push((entry.component.id.to_lowercase(), entry));
+                                }
+                                let removed = msg.removed_pairs.keys().map(|x| x.to_lowercase()).collect::<Vec<String>>();
+                                // ===== Block keys and components written in a single transaction, readers never mix two blocks =====
+                                let mut tx = shared::data::block(network.name.clone(), msg.block_number, StreamState::Running, components_to_update.clone());
+                                tx.hset(key.as_str(), entries).hdel(key.as_str(), removed);
+                                let _ = shared::data::commit(&store, tx).await;
+                            }
+                            supervisor.state(StreamState::Running);
+                            // ===== Notify subscribers (SSE), no error if nobody is listening =====
+                            let event = BlockEvent {
+                                block: msg.block_number,
+                                updated: msg.states.keys().map(|x| x.to_lowercase()).collect(),
+                                added: msg.new_pairs.values().map(|x| SrzProtocolComponent::from(x.clone())).collect(),
+                                removed: msg.removed_pairs.keys().map(|x| x.to_lowercase()).collect(),
+                                state: StreamState::Running,
+                            };
+                            // ===== Journal entry, for consumers resuming after a disconnect =====
+                            let key = keys::stream::journal(network.name.clone());
+                            let _ = shared::data::journal(&store, key.as_str(), &event).await;
+                            // ===== Invalidate the orderbooks cached by every replica =====
+                            let invalidation = Invalidation {
+                                block: msg.block_number,
+                                components: event.updated.iter().chain(event.removed.iter()).cloned().collect(),
+                            };
+                            shared::invalidation::publish(&store, network.clone(), &invalidation).await;
+                            let _ = events.send(event);
+                            // ===== Checkpoint, restored at the next launch =====
+                            if config.checkpoint_interval > 0 && checkpointed.elapsed().as_secs() >= config.checkpoint_interval {
+                                checkpointed = std::time::Instant::now();
+                                match shared::data::checkpoint(&store, network.name.clone(), msg.block_number, srztokens.clone()).await {
+                                    Ok(count) => tracing::debug!("Checkpoint of {} at block {} with {} components", network.name, msg.block_number, count),
+                                    Err(e) => tracing::error!("Failed to checkpoint {} at block {}: {}", network.name, msg.block_number, e),
+                                }
                             }
                         }
                         Err(e) => {
                             tracing::warn!("Error receiving BlockUpdate from stream on {}: {:?}.", network.name, e.to_string());
-                            shared::data::set(keys::stream::status(network.name.clone()).as_str(), StreamState::Error as u128).await;
-                            break;
+                            let _ = shared::data::set(&store, keys::stream::status(network.name.clone()).as_str(), StreamState::Error as u128).await;
+                            supervisor.message(None);
+                            supervisor.state(StreamState::Error);
+                            return Err(format!("Error receiving BlockUpdate: {}", e));
                         }
                     };
                 }
                 None => {
                     tracing::warn!("Stream ended on network {}. Exiting stream session.", network.name);
-                    break;
+                    return Ok(());
                 }
             };
         }
@@ -161,6 +239,139 @@ async fn stream(network: Network, cache: SharedTychoStreamState, config: EnvAPIC
 
 pub type Cache = Arc<RwLock<HashMap<String, Arc<RwLock<TychoStreamState>>>>>;
 
+/// One BlockEvent channel per network, fed by the stream and consumed by the API (SSE)
+pub type Events = HashMap<String, broadcast::Sender<BlockEvent>>;
+
+/// Token list of a network, shared by the stream supervisor and the refresher
+pub type Tokens = Arc<RwLock<Vec<Token>>>;
+
+/// Fetch the token list of a network, with the quality thresholds of its StreamFilters if any (SDK defaults otherwise)
+async fn fetch(network: &Network, config: &EnvAPIConfig, filters: &StreamFilters) -> Option<Vec<Token>> {
+    if !filters.tokens() {
+        return client::tokens(network, config.tycho_api_key.clone()).await;
+    }
+    let chain = Chain::from_str(network.name.as_str()).ok()?;
+    let tokens = load_all_tokens(
+        network.tycho.as_str(),
+        false,
+        Some(config.tycho_api_key.as_str()),
+        chain,
+        filters.min_token_quality,
+        filters.max_days_since_last_trade,
+    )
+    .await;
+    Some(tokens.into_values().collect())
+}
+
+/// Refresh the token list of a network every TOKENS_REFRESH_INTERVAL seconds
+/// Changes are written to the store (stream:tokens and the new tokens feed), new tokens restart the stream to include them
+async fn refresh(network: Network, store: Store, config: EnvAPIConfig, tokens: Tokens, supervisor: Arc<Supervisor>) {
+    let mut ticker = tokio::time::interval(tokio::time::Duration::from_secs(config.tokens_refresh));
+    ticker.tick().await; // First tick is immediate, the list was just fetched
+    loop {
+        ticker.tick().await;
+        let Some(fresh) = fetch(&network, &config, &StreamFilters::from_env(network.name.as_str())).await else {
+            tracing::error!("Failed to refresh tokens for network {}", network.name);
+            continue;
+        };
+        let current = tokens.read().await.iter().map(|t| SrzToken::from(t.clone())).collect::<Vec<SrzToken>>();
+        let known = current.iter().map(|t| t.address.to_lowercase()).collect::<HashSet<String>>();
+        let listed = fresh.iter().map(|t| SrzToken::from(t.clone())).collect::<Vec<SrzToken>>();
+        let addresses = listed.iter().map(|t| t.address.to_lowercase()).collect::<HashSet<String>>();
+        let added = listed.iter().filter(|t| !known.contains(&t.address.to_lowercase())).cloned().collect::<Vec<SrzToken>>();
+        let removed = current.iter().map(|t| t.address.to_lowercase()).filter(|x| !addresses.contains(x)).collect::<Vec<String>>();
+        if added.is_empty() && removed.is_empty() {
+            tracing::debug!("Token list of {} unchanged ({} tokens)", network.name, listed.len());
+            continue;
+        }
+        tracing::info!("Token list of {} changed: + {} tokens, - {} tokens", network.name, added.len(), removed.len());
+        *tokens.write().await = fresh;
+        let _ = shared::data::set(&store, keys::stream::tokens(network.name.clone()).as_str(), listed).await;
+        let _ = shared::data::token_diff(&store, keys::stream::token_feed(network.name.clone()).as_str(), &added, &removed).await;
+        if !added.is_empty() {
+            // Pools of the new tokens are only streamed by a stream built with them
+            supervisor.reload();
+        }
+    }
+}
+
+/// Run the stream of a network forever, restarting it according to its RestartPolicy
+/// Exits, panics included, and restarts are recorded by the supervisor, the restart state is also written to the store (shown on /status)
+async fn supervise(network: Network, states: Cache, store: Store, events: broadcast::Sender<BlockEvent>, config: EnvAPIConfig, tokens: Tokens, supervisor: Arc<Supervisor>) {
+    let policy = RestartPolicy::from_env(network.name.as_str(), config.testing);
+    tracing::debug!("Restart policy for {}: {:?}", network.name, policy);
+    let key = keys::stream::restart(network.name.clone());
+    let mut info = RestartInfo::default();
+    loop {
+        tracing::debug!("Launching stream for network {}", network.name);
+        let state = {
+            let map = states.read().await;
+            map.get(&network.name).expect("State must be present").clone()
+        };
+        // Latest token list, kept up to date by the refresher
+        // Read before the token list, so that a refresh in between restarts the session
+        let generation = supervisor.generation();
+        let list = tokens.read().await.clone();
+        let started = std::time::Instant::now();
+        let streaming = AssertUnwindSafe(stream(network.clone(), state, store.clone(), events.clone(), config.clone(), list, supervisor.clone(), generation))
+            .catch_unwind()
+            .await;
+        let error = match streaming {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e),
+            Err(e) => {
+                let msg = e
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or(e.downcast_ref::<&str>().map(|x| x.to_string()))
+                    .unwrap_or("unknown panic payload".to_string());
+                Some(format!("Stream panicked: {}", msg))
+            }
+        };
+        // Session ended by a token list refresh: restarted right away, counted apart from clean ends and failures
+        let reloaded = error.is_none() && supervisor.generation() != generation;
+        if reloaded {
+            info.reloads += 1;
+        } else {
+            info.restarts += 1;
+        }
+        // A session lasting longer than the max delay was stable, the backoff starts over
+        if started.elapsed().as_secs() >= policy.max {
+            info.attempt = 0;
+        }
+        let delay = match error {
+            None if reloaded => {
+                tracing::debug!("Stream for {} reloaded. Restarting right away...", network.name);
+                info.attempt = 0;
+                0
+            }
+            None => {
+                // Base delay, so that a stream ending right after it starts doesn't reconnect in a tight loop
+                info.attempt = 0;
+                let delay = policy.delay(1);
+                tracing::debug!("Stream for {} ended normally. Restarting in {} seconds...", network.name, delay);
+                delay
+            }
+            Some(e) => {
+                info.attempt += 1;
+                let delay = policy.delay(info.attempt);
+                tracing::error!("Stream for {} failed (attempt {}): {}. Restarting in {} seconds...", network.name, info.attempt, e, delay);
+                info.last_error = Some(e);
+                info.last_error_at = Some(current_timestamp());
+                delay
+            }
+        };
+        info.next_retry_at = Some(current_timestamp() + delay);
+        supervisor.state(StreamState::Down);
+        supervisor.restart(info.clone());
+        let _ = shared::data::set(&store, key.as_str(), info.clone()).await;
+        tokio::time::sleep(tokio::time::Duration::from_secs(delay)).await;
+        info.next_retry_at = None;
+        supervisor.restart(info.clone());
+        let _ = shared::data::set(&store, key.as_str(), info.clone()).await;
+    }
+}
+
 /// Stream the entire state from each AMMs, with TychoStreamBuilder.
 #[tokio::main]
 async fn main() {
@@ -178,26 +389,66 @@ async fn main() {
     let networks = networks.into_iter().filter(|x| x.name != "base").collect::<Vec<Network>>();
     let targets = config.networks.clone();
     let networks = networks.into_iter().filter(|x| targets.contains(&x.name.to_lowercase())).collect::<Vec<Network>>();
-    shared::data::ping().await;
+    let store = shared::store::from_env();
+    shared::data::ping(&store).await;
+    if let Err(e) = shared::data::schema(&store, config.drop_legacy_keys).await {
+        tracing::error!("📕 {}", e);
+        std::process::exit(1);
+    }
+    // --- Warm restart: serve the latest checkpoint (stale) until the stream is running ---
+    let mut checkpoints = HashMap::new();
     for network in networks.clone() {
-        shared::data::set(keys::stream::status(network.name.clone()).as_str(), StreamState::Launching as u128).await;
-        shared::data::set(keys::stream::latest(network.name.clone().to_string()).as_str(), 0).await;
+        let key = keys::stream::checkpoint(network.name.clone());
+        let tx = match shared::data::get::<Checkpoint>(&store, key.as_str()).await {
+            Ok(checkpoint) => {
+                tracing::info!(
+                    "Restoring checkpoint of {} at block {} ({} tokens, {} components)",
+                    network.name,
+                    checkpoint.block,
+                    checkpoint.tokens.len(),
+                    checkpoint.components.len()
+                );
+                checkpoints.insert(network.name.clone(), checkpoint.components.clone());
+                shared::data::restore(network.name.clone(), checkpoint)
+            }
+            Err(e) => {
+                tracing::info!("No checkpoint restored for {}: {}", network.name, e);
+                shared::data::block(network.name.clone(), 0, StreamState::Launching, vec![])
+            }
+        };
+        let _ = shared::data::commit(&store, tx).await;
     }
     // --- Heartbeat ---
-    shared::helpers::hearbeats(networks.clone(), config.clone()).await;
+    shared::helpers::hearbeats(store.clone(), networks.clone(), config.clone()).await;
 
     // --- Create a cache for the shared state, this is the key to share the state between streams and API tasks ---
     let cache: Arc<RwLock<HashMap<String, SharedTychoStreamState>>> = Arc::new(RwLock::new(HashMap::new()));
 
     // --- Initialize state for each network ---
     for net in &networks {
-        cache.write().await.insert(
+        let mut state = TychoStreamState {
+            protosims: HashMap::new(),
+            components: HashMap::new(),
+            initialised: false,
+        };
+        // Components of the checkpoint, used by /execute until the first BlockUpdate
+        if let Some(components) = checkpoints.remove(&net.name) {
+            shared::sync::restore(&mut state, &components);
+        }
+        cache.write().await.insert(net.name.clone(), Arc::new(RwLock::new(state)));
+    }
+    // --- Create a BlockEvent channel for each network ---
+    let mut events: Events = HashMap::new();
+    for net in &networks {
+        let (tx, _) = broadcast::channel::<BlockEvent>(BLOCK_EVENTS_CAPACITY);
+        events.insert(net.name.clone(), tx);
+    }
+    // --- Create a supervisor for each network, reporting the lifecycle of its stream task ---
+    let mut supervisors: Supervisors = HashMap::new();
+    for net in &networks {
+        supervisors.insert(
             net.name.clone(),
-            Arc::new(RwLock::new(TychoStreamState {
-                protosims: HashMap::new(),
-                components: HashMap::new(),
-                initialised: false,
-            })),
+            Arc::new(Supervisor::new(net.name.clone(), events.get(&net.name).expect("Events channel must be present").clone())),
         );
     }
     let readable = Arc::clone(&cache);
@@ -207,7 +458,7 @@ async fn main() {
     // --- Fetch tokens for each network ---
     let mut atks = HashMap::new();
     for network in networks.clone() {
-        let tokens = match client::tokens(&network, config.tycho_api_key.clone()).await {
+        let tokens = match fetch(&network, &config, &StreamFilters::from_env(network.name.as_str())).await {
             Some(t) => t,
             None => {
                 tracing::error!("Failed to get tokens for network {}", network.name);
@@ -218,33 +469,16 @@ async fn main() {
     }
     tracing::debug!("Spawning stream tasks for network");
     for network in networks {
-        let config = config.clone();
-        let states = Arc::clone(&cache);
-        let tokens = atks.get(&network.name).expect("Tokens must be present").clone();
+        let tokens: Tokens = Arc::new(RwLock::new(atks.get(&network.name).expect("Tokens must be present").clone()));
+        let events = events.get(&network.name).expect("Events channel must be present").clone();
         tracing::info!("Tycho client built successfully for network {}", network.name);
-        tokio::spawn(async move {
-            loop {
-                tracing::debug!("Launching stream for network {}", network.name);
-                let state = {
-                    let map = states.read().await;
-                    map.get(&network.name).expect("State must be present").clone()
-                };
-                let streaming = AssertUnwindSafe(stream(network.clone(), state, config.clone(), tokens.clone())).catch_unwind().await;
-                match streaming {
-                    Ok(_) => {
-                        tracing::debug!("Stream for {} ended normally. Restarting...", network.name);
-                    }
-                    Err(e) => {
-                        tracing::error!("Stream for {} panicked: {:?}. Restarting...", network.name, e);
-                    }
-                }
-                let delay = if config.testing { RESTART_STREAM_DELAY / 10 } else { RESTART_STREAM_DELAY };
-                tracing::debug!("Waiting {} seconds before restarting stream for {}", delay, network.name);
-                tokio::time::sleep(tokio::time::Duration::from_secs(delay)).await;
-            }
-        });
+        let supervisor = supervisors.get(&network.name).expect("Supervisor must be present").clone();
+        if config.tokens_refresh > 0 {
+            tokio::spawn(refresh(network.clone(), store.clone(), config.clone(), tokens.clone(), supervisor.clone()));
+        }
+        tokio::spawn(supervise(network, Arc::clone(&cache), store.clone(), events, config.clone(), tokens, supervisor));
     }
     // --- Spawn the Axum server ---
     tokio::time::sleep(tokio::time::Duration::from_millis(2500)).await; // Wait streams init
-    axum::start(dupnets.clone(), Arc::clone(&readable), dupc.clone()).await;
+    axum::start(dupnets.clone(), Arc::clone(&readable), store, events, supervisors, dupc.clone()).await;
 }